@@ -0,0 +1,232 @@
+//! matching pallets the mod manager does not track to the mod.io mods they came from
+
+use std::path::PathBuf;
+
+use modio::filter::prelude::*;
+use modio::mods::filters::Fulltext;
+use modio::{Modio, files::File, files::filters::Id as fid, mods::Mod, types::id::Id};
+
+use crate::client::{BONELAB, is_for, take_live_modfile};
+use crate::manifest::{Untracked, UntrackedPallets, find_untracked, make_manifest, pallet_files};
+use crate::plan::{Action, Plan};
+use crate::structs::{Manifest, Platform};
+use crate::throw;
+
+/// lowercase letters and digits only, so "Cool Gun" matches "CoolGun"
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|x| x.is_alphanumeric())
+        .flat_map(|x| x.to_lowercase())
+        .collect()
+}
+
+/// search mod.io for the mod a pallet came from, by title and then by barcode
+///
+/// more than one mod is returned when the title matches several and the author does not settle it
+pub async fn match_pallet(
+    modio: &Modio,
+    pallet: &Untracked,
+) -> Result<Vec<Mod>, Box<dyn std::error::Error>> {
+    // barcodes are usually Author.Title
+    let mut parts = pallet.barcode.split('.');
+    let barcode_author = parts.next().unwrap_or_default();
+    let barcode_title = parts.next_back().unwrap_or(&pallet.barcode);
+    let author = normalize(pallet.author.as_deref().unwrap_or(barcode_author));
+    let mut titles = Vec::new();
+    if let Some(title) = &pallet.title {
+        titles.push(title.as_str());
+    }
+    titles.push(barcode_title);
+
+    for title in titles {
+        let filter = Fulltext::eq(title).limit(20);
+        let found = modio
+            .game(Id::new(BONELAB))
+            .mods()
+            .search(filter)
+            .first_page()
+            .await?;
+        let found: Vec<Mod> = found
+            .into_iter()
+            .filter(|x| normalize(&x.name) == normalize(title))
+            .collect();
+        if found.len() > 1 {
+            let by_author: Vec<&Mod> = found
+                .iter()
+                .filter(|x| normalize(&x.submitted_by.username) == author)
+                .collect();
+            if by_author.len() == 1 {
+                let id = by_author[0].id;
+                return Ok(found.into_iter().filter(|x| x.id == id).collect());
+            }
+        }
+        if !found.is_empty() {
+            return Ok(found);
+        }
+    }
+    Ok(Vec::new())
+}
+
+/// the modfile matching the installed version, or the live one if the version is unknown
+async fn adopted_modfile(
+    modio: &Modio,
+    mod_: &mut Mod,
+    version: &Option<String>,
+    platform: Platform,
+) -> Result<Option<File>, Box<dyn std::error::Error>> {
+    if let Some(version) = version {
+        let files = modio
+            .mod_(Id::new(BONELAB), mod_.id)
+            .files()
+            .search(fid::desc())
+            .collect()
+            .await?;
+        if let Some(x) = files
+            .into_iter()
+            .find(|x| is_for(x, platform) && x.version.as_deref() == Some(version.as_str()))
+        {
+            return Ok(Some(x));
+        }
+    }
+    take_live_modfile(modio, mod_, platform).await
+}
+
+/// pallets to adopt, only `only` if given
+///
+/// local test builds and the game's own pallets are adopted only when asked for by barcode,
+/// a title match would let updates replace built-in content
+pub fn adoptable(
+    path: &str,
+    platform: Platform,
+    only: &Option<String>,
+) -> Result<UntrackedPallets, Box<dyn std::error::Error>> {
+    let mut untracked = find_untracked(path, platform)?;
+    untracked.pallets.retain(|x| match only {
+        Some(only) => x.barcode == *only,
+        None => {
+            !x.barcode.starts_with("SLZ.") && !x.manifest.as_ref().is_some_and(|x| x.pallet().local)
+        }
+    });
+    if let Some(x) = only
+        && untracked.pallets.is_empty()
+    {
+        throw(&format!("{} is not an untracked pallet", x))?;
+    }
+    Ok(untracked)
+}
+
+/// why a pallet was not adopted
+pub enum Skipped {
+    /// no mod on mod.io matches it
+    NoMatch,
+    /// more than one mod matches it
+    Ambiguous(Vec<Mod>),
+    /// the matching mod, named here, has no modfile for the platform
+    NoModfile(String),
+    /// the pallet folder has no pallet or catalog json
+    NoPalletFiles,
+}
+
+/// the manifests that adopt pallets, see `plan_adoption`
+pub struct Adoption {
+    /// one manifest write per adopted pallet
+    pub plan: Plan,
+    /// the manifests to write
+    pub manifests: Vec<Manifest>,
+    /// barcodes of the pallets that were not adopted, and why
+    pub skipped: Vec<(String, Skipped)>,
+    /// barcodes and versions of pallets no modfile has the version of, the live modfile is assumed
+    pub assumed_live: Vec<(String, Option<String>)>,
+}
+
+/// match pallets to mod.io mods, or all of them to `mod_id`, and make their manifests
+///
+/// what the game already knows about a pallet, like its paths and whether it is active, is kept
+pub async fn plan_adoption(
+    modio: &Modio,
+    untracked: &[Untracked],
+    mod_id: Option<u64>,
+    path: &str,
+    platform: Platform,
+) -> Result<Adoption, Box<dyn std::error::Error>> {
+    let mut adoption = Adoption {
+        plan: Plan::default(),
+        manifests: Vec::new(),
+        skipped: Vec::new(),
+        assumed_live: Vec::new(),
+    };
+    for pallet in untracked {
+        let mut mod_ = match mod_id {
+            Some(id) => modio.mod_(Id::new(BONELAB), Id::new(id)).get().await?,
+            None => {
+                let mut found = match_pallet(modio, pallet).await?;
+                match found.len() {
+                    0 => {
+                        adoption
+                            .skipped
+                            .push((pallet.barcode.clone(), Skipped::NoMatch));
+                        continue;
+                    }
+                    1 => found.remove(0),
+                    _ => {
+                        adoption
+                            .skipped
+                            .push((pallet.barcode.clone(), Skipped::Ambiguous(found)));
+                        continue;
+                    }
+                }
+            }
+        };
+        let modfile = match adopted_modfile(modio, &mut mod_, &pallet.version, platform).await? {
+            Some(x) => x,
+            None => {
+                adoption
+                    .skipped
+                    .push((pallet.barcode.clone(), Skipped::NoModfile(mod_.name)));
+                continue;
+            }
+        };
+        if modfile.version != pallet.version {
+            adoption
+                .assumed_live
+                .push((pallet.barcode.clone(), pallet.version.clone()));
+        }
+        let folder = PathBuf::from(path).join(&pallet.barcode);
+        let (pallet_name, catalog_name) = match pallet_files(&folder)? {
+            Some(x) => x,
+            None => {
+                adoption
+                    .skipped
+                    .push((pallet.barcode.clone(), Skipped::NoPalletFiles));
+                continue;
+            }
+        };
+        let mut manifest = make_manifest(
+            &mod_,
+            &modfile,
+            &pallet.barcode,
+            &pallet_name,
+            &catalog_name,
+            None,
+            platform,
+        );
+        // keep what the game already knows about the pallet
+        if let Some(old) = &pallet.manifest {
+            manifest.pallet_mut().palletPath = old.pallet().palletPath.clone();
+            manifest.pallet_mut().catalogPath = old.pallet().catalogPath.clone();
+            manifest.pallet_mut().installedDate = old.pallet().installedDate.clone();
+            manifest.pallet_mut().active = old.pallet().active;
+            manifest.pallet_mut().extra = old.pallet().extra.clone();
+            manifest.extra = old.extra.clone();
+        }
+        adoption.plan.actions.push(Action::WriteManifest(format!(
+            "{} as {} {} (mod {})",
+            pallet.barcode,
+            mod_.name,
+            modfile.version.clone().unwrap_or_default(),
+            mod_.id
+        )));
+        adoption.manifests.push(manifest);
+    }
+    Ok(adoption)
+}
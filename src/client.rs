@@ -0,0 +1,335 @@
+//! logging in to mod.io and the requests the mod manager makes through the modio crate
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{self, Write},
+    time::Duration,
+};
+
+use modio::filter::prelude::*;
+use modio::mods::filters::{Fulltext, GameId, Name};
+use modio::{Credentials, Modio, auth::Token, types::id::Id};
+use modio::{files::filters::Id as fid, mods::Mod, mods::filters::Id as mid};
+
+use crate::state::Availability;
+use crate::structs::Platform;
+use crate::throw;
+
+/// mod.io game id of bonelab
+pub const BONELAB: u64 = 3809;
+/// base url of the mod.io rest api, for the few requests the modio crate cannot make
+pub const MODIO_API: &str = "https://api.mod.io/v1";
+
+/// log in to mod.io with the api key and the saved oauth token
+///
+/// without a saved token a security code is emailed and `security_code` is asked for it,
+/// the resulting token is saved under `xdg_config_home`, returns the client and the name of the user
+pub async fn login(
+    api_key: Option<String>,
+    email: Option<&str>,
+    xdg_config_home: &str,
+    security_code: impl FnOnce() -> io::Result<String>,
+) -> Result<(Modio, String), Box<dyn std::error::Error>> {
+    let xdg_config_home = xdg_config_home.to_string();
+    let mut modio = Modio::new(Credentials::new(match api_key {
+        Some(x) => x,
+        None => {
+            match fs::read_to_string(xdg_config_home.clone() + "/bonelab-mod-manager/modio_api_key")
+            {
+                Ok(x) => x,
+                Err(_) => throw("Missing modio api key")?,
+            }
+        }
+    }))?;
+
+    let access_token =
+        fs::read_to_string(xdg_config_home.clone() + "/bonelab-mod-manager/modio_access_token");
+    if let Ok(token) = access_token {
+        let token = Token {
+            value: token,
+            expired_at: None,
+        };
+        modio = modio.with_token(token);
+    } else {
+        modio
+            .auth()
+            .request_code(match email {
+                Some(x) => x,
+                None => throw("not logged in, log in with -e and your email")?,
+            })
+            .await?;
+        let code = security_code()?;
+        let creds = modio.auth().security_code(&code).await?;
+        let token = match &creds.token {
+            Some(x) => x,
+            None => throw("could not log in, mod.io did not return a token")?,
+        };
+        fs::create_dir_all(xdg_config_home.clone() + "/bonelab-mod-manager")?;
+        let mut file =
+            File::create(xdg_config_home.clone() + "/bonelab-mod-manager/modio_access_token")?;
+        file.write_all(token.value.as_bytes())?;
+        modio = modio.with_token(token.clone());
+    }
+    let user = match modio.user().current().await? {
+        Some(x) => x,
+        None => throw("could not log in, mod.io does not know the token")?,
+    };
+    Ok((modio, user.username))
+}
+
+/// the oauth token `login` saved
+pub fn access_token(xdg_config_home: &str) -> Result<String, Box<dyn std::error::Error>> {
+    match fs::read_to_string(
+        xdg_config_home.to_string() + "/bonelab-mod-manager/modio_access_token",
+    ) {
+        Ok(x) => Ok(x.trim().to_string()),
+        Err(_) => throw("Missing modio access token, log in with -e first"),
+    }
+}
+
+/// check if a modfile was uploaded for a platform
+pub fn is_for(file: &modio::files::File, platform: Platform) -> bool {
+    file.platforms.iter().any(|x| x.target == platform.modio())
+}
+
+/// get mods from mod.io, up to 100 per request
+pub async fn fetch_mods(
+    modio: &Modio,
+    ids: &[u64],
+) -> Result<HashMap<u64, Mod>, Box<dyn std::error::Error>> {
+    let mut mods = HashMap::new();
+    for chunk in ids.chunks(100) {
+        let filter = mid::_in(chunk.to_vec()).limit(100);
+        let mut delay = 0;
+        loop {
+            let search = modio.game(Id::new(BONELAB)).mods().search(filter.clone());
+            match search.first_page().await {
+                Ok(page) => {
+                    for mod_ in page {
+                        mods.insert(mod_.id.get(), mod_);
+                    }
+                    break;
+                }
+                Err(x) => {
+                    if !x.is_ratelimited() {
+                        return Err(x.into());
+                    }
+                    delay = 2 * delay + 1;
+                }
+            }
+            tokio::time::sleep(Duration::new(delay, 0)).await;
+        }
+    }
+    Ok(mods)
+}
+
+/// id of the modfile that is live for a platform, mods without platforms only have a pc one
+pub fn live_modfile_id(online_mod: &Mod, platform: Platform) -> Option<u64> {
+    match online_mod
+        .platforms
+        .iter()
+        .find(|x| x.target == platform.modio())
+    {
+        Some(x) => Some(x.modfile_id.get()),
+        None if platform == Platform::Pc => online_mod.modfile.as_ref().map(|x| x.id.get()),
+        None => None,
+    }
+}
+
+/// the live modfile for a platform, only asks mod.io if the mod listing has a different live file
+pub async fn take_live_modfile(
+    modio: &Modio,
+    online_mod: &mut Mod,
+    platform: Platform,
+) -> Result<Option<modio::files::File>, Box<dyn std::error::Error>> {
+    let live = match live_modfile_id(online_mod, platform) {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+    if online_mod
+        .modfile
+        .as_ref()
+        .is_some_and(|x| x.id.get() == live)
+    {
+        return Ok(online_mod.modfile.take());
+    }
+    let modref = modio.mod_(Id::new(BONELAB), online_mod.id);
+    Ok(Some(modref.file(Id::new(live)).get().await?))
+}
+
+/// ids of the mods that have modfile or availability events since a unix time
+pub async fn changed_since(
+    modio: &Modio,
+    ids: &[u64],
+    since: i64,
+) -> Result<HashSet<u64>, Box<dyn std::error::Error>> {
+    use modio::mods::EventType;
+    use modio::mods::filters::events::{DateAdded, EventType as EventTypeFilter, ModId};
+
+    let mut changed = HashSet::new();
+    for chunk in ids.chunks(100) {
+        let filter =
+            ModId::_in(chunk.to_vec())
+                .and(DateAdded::gt(since))
+                .and(EventTypeFilter::_in(vec![
+                    EventType::MODFILE_CHANGED,
+                    EventType::MOD_DELETED,
+                    EventType::MOD_UNAVAILABLE,
+                    EventType::MOD_AVAILABLE,
+                ]));
+        let mut delay = 0;
+        loop {
+            let events = modio.game(Id::new(BONELAB)).mods().events(filter.clone());
+            match events.collect().await {
+                Ok(events) => {
+                    changed.extend(events.iter().map(|x| x.mod_id.get()));
+                    break;
+                }
+                Err(x) => {
+                    if !x.is_ratelimited() {
+                        return Err(x.into());
+                    }
+                    delay = 2 * delay + 1;
+                }
+            }
+            tokio::time::sleep(Duration::new(delay, 0)).await;
+        }
+    }
+    Ok(changed)
+}
+
+/// look up a mod that was not in the search results, it is either not public or gone
+pub async fn find_missing_mod(
+    modio: &Modio,
+    mod_id: u64,
+) -> Result<Result<Mod, Availability>, modio::Error> {
+    let modref = modio.mod_(Id::new(BONELAB), Id::new(mod_id));
+    let mut delay = 0;
+    loop {
+        match modref.clone().get().await {
+            Ok(x) => {
+                if x.status == modio::mods::Status::DELETED {
+                    return Ok(Err(Availability::Deleted));
+                }
+                if x.status == modio::mods::Status::NOT_ACCEPTED {
+                    return Ok(Err(Availability::NotAccepted));
+                }
+                if x.visible == modio::mods::Visibility::HIDDEN {
+                    return Ok(Err(Availability::Hidden));
+                }
+                return Ok(Ok(x));
+            }
+            Err(x) => match x.status().map(|x| x.as_u16()) {
+                Some(404) | Some(410) => return Ok(Err(Availability::Deleted)),
+                Some(401) | Some(403) => return Ok(Err(Availability::Unauthorized)),
+                _ => {
+                    if !x.is_ratelimited() {
+                        return Err(x);
+                    }
+                    delay = 2 * delay + 1;
+                }
+            },
+        }
+        tokio::time::sleep(Duration::new(delay, 0)).await;
+    }
+}
+
+/// the bonelab mods the logged in user is subscribed to, by name
+pub async fn subscribed_mods(modio: &Modio) -> Result<Vec<Mod>, Box<dyn std::error::Error>> {
+    let filter = GameId::_in(BONELAB).and(Name::asc());
+    Ok(modio.user().subscriptions(filter).collect().await?)
+}
+
+/// search mod.io for mods that could provide a pallet, by the title part of its barcode
+pub async fn search_pallet(
+    modio: &Modio,
+    barcode: &str,
+) -> Result<Vec<Mod>, Box<dyn std::error::Error>> {
+    // barcodes are usually Author.Title
+    let title = barcode.rsplit('.').next().unwrap_or(barcode);
+    let filter = Fulltext::eq(title).limit(5);
+    Ok(modio
+        .game(Id::new(BONELAB))
+        .mods()
+        .search(filter)
+        .first_page()
+        .await?)
+}
+
+/// subscribe or unsubscribe, waiting out rate limits
+pub async fn set_subscribed(
+    modio: &Modio,
+    mod_id: u64,
+    subscribed: bool,
+) -> std::result::Result<(), modio::Error> {
+    let modref = modio.mod_(Id::new(BONELAB), Id::new(mod_id));
+    let mut delay = 0;
+    loop {
+        let result = match subscribed {
+            true => modref.clone().subscribe().await,
+            false => modref.clone().unsubscribe().await,
+        };
+        match result {
+            Err(x) if x.is_ratelimited() => {
+                delay = 2 * delay + 1;
+                tokio::time::sleep(Duration::new(delay, 0)).await;
+            }
+            x => return x,
+        }
+    }
+}
+
+/// parse mod.io version strings like "v1.2" as semver
+pub fn parse_version(version: &str) -> Option<semver::Version> {
+    let version = version.trim().trim_start_matches(['v', 'V']);
+    if let Ok(x) = semver::Version::parse(version) {
+        return Some(x);
+    }
+    let mut parts: Vec<&str> = version.split('.').collect();
+    while parts.len() < 3 {
+        parts.push("0");
+    }
+    semver::Version::parse(&parts.join(".")).ok()
+}
+
+/// check a modfile version against an optional semver requirement
+pub fn version_matches(requirement: &Option<semver::VersionReq>, version: &Option<String>) -> bool {
+    let requirement = match requirement {
+        Some(x) => x,
+        None => return true,
+    };
+    match version.as_deref().and_then(parse_version) {
+        Some(x) => requirement.matches(&x),
+        None => false,
+    }
+}
+
+/// pick the newest modfile of a mod for a platform that matches the version requirement
+pub async fn resolve_modfile(
+    modio: &Modio,
+    mod_id: u64,
+    requirement: &Option<semver::VersionReq>,
+    platform: Platform,
+) -> Result<modio::files::File, Box<dyn std::error::Error>> {
+    let modref = modio.mod_(Id::new(BONELAB), Id::new(mod_id));
+    if requirement.is_none() {
+        let mut online_mod = modref.clone().get().await?;
+        if let Some(modfile) = take_live_modfile(modio, &mut online_mod, platform).await?
+            && is_for(&modfile, platform)
+        {
+            return Ok(modfile);
+        }
+    }
+    let files = modref.files().search(fid::desc()).collect().await?;
+    match files
+        .into_iter()
+        .find(|file| is_for(file, platform) && version_matches(requirement, &file.version))
+    {
+        Some(x) => Ok(x),
+        None => throw(&format!(
+            "no {} modfile of mod {} matches the version",
+            platform, mod_id
+        )),
+    }
+}
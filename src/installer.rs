@@ -0,0 +1,827 @@
+//! downloading, unpacking, installing and removing pallets
+
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use indicatif::ProgressBar;
+use modio::{DownloadAction, Modio, mods::Mod, types::id::Id};
+
+use crate::client::{BONELAB, live_modfile_id, take_live_modfile};
+use crate::manifest::{
+    InstalledMod, make_local_manifest, make_manifest, pallet_files, read_pallet_json, save_manifest,
+};
+use crate::plan::{Action, Plan};
+use crate::state::State;
+use crate::structs::{Manifest, Platform};
+use crate::{throw, xdg_dir};
+
+/// what `download_mod` installs, everything left out is made from the mod listing
+#[derive(Default)]
+pub struct DownloadOptions<'a> {
+    /// the modfile to install, the live one for the platform if none is given
+    pub modfile: Option<&'a modio::files::File>,
+    /// replaces the manifest that would be made from the mod listing
+    pub manifest: Option<Manifest>,
+    /// install date of the copy it replaces, in unix milliseconds
+    pub installed_date: Option<u128>,
+}
+
+/// download a modfile and install it into the mods folder over any older copy
+pub async fn download_mod(
+    mod_: &Mod,
+    modio: &Modio,
+    path: &Path,
+    platform: Platform,
+    options: DownloadOptions<'_>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let fetched;
+    let modfile = match options.modfile {
+        Some(x) => x,
+        None => match live_modfile_id(mod_, platform) {
+            Some(id) if mod_.modfile.as_ref().is_some_and(|x| x.id.get() == id) => {
+                mod_.modfile.as_ref().unwrap()
+            }
+            Some(id) => {
+                let modref = modio.mod_(Id::new(BONELAB), mod_.id);
+                fetched = modref.file(Id::new(id)).get().await?;
+                &fetched
+            }
+            None => throw(&format!("{} has no {} modfile", mod_.name, platform))?,
+        },
+    };
+    let archive = download_modfile(mod_, modio, modfile).await?;
+    let staged = stage_archive(&archive, &path.to_string_lossy(), &mod_.name)?;
+    let mani = match options.manifest {
+        Some(x) => x,
+        None => make_manifest(
+            mod_,
+            modfile,
+            &staged.barcode,
+            &staged.pallet_name,
+            &staged.catalog_name,
+            options.installed_date,
+            platform,
+        ),
+    };
+    install_staged(path, &staged, &mani)?;
+    // thumbnails are nice to have, a failed download does not fail the install
+    let _ = cache_thumbnail(mod_.id.get(), mod_.logo.thumb_320x180.as_str()).await;
+    Ok(())
+}
+
+/// download a modfile into the cache unless it is already there, returns the zip path
+pub async fn download_modfile(
+    mod_: &Mod,
+    modio: &Modio,
+    modfile: &modio::files::File,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let xdg_cache_home = xdg_dir("XDG_CACHE_HOME", ".cache")?;
+
+    let action = DownloadAction::File {
+        game_id: Id::new(BONELAB),
+        mod_id: Id::new(mod_.id.into()),
+        file_id: Id::new(modfile.id.into()),
+    };
+    fs::create_dir_all(xdg_cache_home.clone() + "/bonelab-mod-manager")?;
+    // archives are kept per modfile so rollbacks can reuse them
+    let archive = format!(
+        "{}/bonelab-mod-manager/{}_{}.zip",
+        &xdg_cache_home, mod_.id, modfile.id
+    );
+    let cached = PathBuf::from(&archive).exists() && md5_file(&archive)? == modfile.filehash.md5;
+    if !cached {
+        modio.download(action).await?.save_to_file(&archive).await?;
+    }
+    if md5_file(&archive)? != modfile.filehash.md5 {
+        throw(&format!(
+            "md5 of the download for {} does not match mod.io",
+            mod_.name
+        ))?;
+    }
+    Ok(archive)
+}
+
+/// md5 of a file as a hex string
+pub fn md5_file(file: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let output = Command::new("md5sum").arg(file).output()?;
+    let output = String::from_utf8(output.stdout)?;
+    match output.split_whitespace().next() {
+        Some(x) => Ok(x.to_string()),
+        None => throw("Failed to execute command: md5sum"),
+    }
+}
+
+/// a pallet unpacked next to the mods folder, waiting to be moved in
+pub struct Staged {
+    /// the folder holding the barcode folder
+    pub folder: String,
+    /// barcode of the pallet, also the name of its folder
+    pub barcode: String,
+    /// file name of the pallet json
+    pub pallet_name: String,
+    /// file name of the catalog json
+    pub catalog_name: String,
+}
+
+/// unzip a pallet archive into path/name
+pub fn stage_archive(
+    archive: &str,
+    path: &str,
+    name: &str,
+) -> Result<Staged, Box<dyn std::error::Error>> {
+    let folder = path.to_string() + "/" + name;
+    let output = Command::new("unzip")
+        .args([archive, "-d", &folder])
+        .output()?;
+    if !output.status.success() {
+        let _ = fs::remove_dir_all(&folder);
+        throw(&format!("Failed to unzip {}", archive))?;
+    }
+    find_staged(folder)
+}
+
+/// copy a pallet folder, or a folder holding one, into path/name
+pub fn stage_dir(dir: &Path, path: &str, name: &str) -> Result<Staged, Box<dyn std::error::Error>> {
+    let folder = path.to_string() + "/" + name;
+    fs::create_dir_all(&folder)?;
+    let output = Command::new("cp")
+        .arg("-r")
+        .arg(dir)
+        .arg(&folder)
+        .output()?;
+    if !output.status.success() {
+        let _ = fs::remove_dir_all(&folder);
+        throw(&format!("Failed to copy {}", dir.display()))?;
+    }
+    // a folder holding the barcode folder is copied one level too deep
+    let pallet = read_pallet_json(dir);
+    if pallet.is_err() {
+        let _ = fs::remove_dir_all(&folder);
+    }
+    if pallet?.is_none() {
+        let copied = PathBuf::from(&folder).join(dir.file_name().unwrap());
+        let _output = Command::new("sh")
+            .arg("-c")
+            .arg("mv \"$0\"/* \"$1\"/ && rmdir \"$0\"")
+            .arg(&copied)
+            .arg(&folder)
+            .output()?;
+    }
+    find_staged(folder)
+}
+
+/// the pallet in a staging folder, renamed after the barcode in its pallet json,
+/// the folder is removed when it does not hold one
+fn find_staged(folder: String) -> Result<Staged, Box<dyn std::error::Error>> {
+    let staged = read_staged(&folder);
+    if staged.is_err() {
+        let _ = fs::remove_dir_all(&folder);
+    }
+    staged
+}
+
+fn read_staged(folder: &str) -> Result<Staged, Box<dyn std::error::Error>> {
+    let name = Command::new("ls").arg(folder).output()?.stdout;
+    let name = String::from_utf8(name)?.trim().to_string();
+    if name.is_empty() || name.contains('\n') {
+        throw(&format!(
+            "{} does not hold exactly one pallet folder",
+            folder
+        ))?;
+    }
+    // the game looks pallets up by the barcode in the pallet json, not by the folder name
+    let pallet_folder = PathBuf::from(folder).join(&name);
+    let barcode = match read_pallet_json(&pallet_folder)? {
+        Some(x) => match x.barcode() {
+            Some(x) => x.to_string(),
+            None => throw(&format!("the pallet json in {} has no barcode", name))?,
+        },
+        None => throw(&format!("{} has no pallet json", name))?,
+    };
+    if barcode.is_empty() || barcode.contains(['/', '\\']) || barcode.starts_with('.') {
+        throw(&format!(
+            "{} is not a barcode a folder can be named after",
+            barcode
+        ))?;
+    }
+    if barcode != name {
+        fs::rename(&pallet_folder, PathBuf::from(folder).join(&barcode))?;
+    }
+    let (pallet_name, catalog_name) = match pallet_files(&PathBuf::from(folder).join(&barcode))? {
+        Some(x) => x,
+        None => throw(&format!("{} has no pallet and catalog json", barcode))?,
+    };
+    Ok(Staged {
+        folder: folder.to_string(),
+        barcode,
+        pallet_name,
+        catalog_name,
+    })
+}
+
+/// write the manifest and move a staged pallet into the mods folder
+pub fn install_staged(
+    path: &Path,
+    staged: &Staged,
+    manifest: &Manifest,
+) -> Result<(), Box<dyn std::error::Error>> {
+    save_manifest(path.to_path_buf(), manifest)?;
+
+    // replace the old version of the pallet if there is one
+    let installed_folder = path.join(&staged.barcode);
+    if installed_folder.exists() {
+        fs::remove_dir_all(installed_folder)?;
+    }
+    let _output = Command::new("mv")
+        .arg(staged.folder.clone() + "/" + &staged.barcode)
+        .arg(path)
+        .output()?;
+    let _output = Command::new("rmdir").arg(&staged.folder).output()?;
+    Ok(())
+}
+
+/// delete the pallet folder and manifest of an installed mod
+pub fn remove_mod(path: &str, mod_: &InstalledMod) -> Result<(), Box<dyn std::error::Error>> {
+    let folder = PathBuf::from(path).join(mod_.barcode());
+    if folder.exists() {
+        fs::remove_dir_all(folder)?;
+    }
+    fs::remove_file(&mod_.path)?;
+    if let Some(id) = mod_.mod_id()
+        && let Some(thumbnail) = cached_thumbnail(id)
+    {
+        fs::remove_file(thumbnail)?;
+    }
+    Ok(())
+}
+
+/// zip the pallet folder and manifest of a mod, returns the path of the zip
+pub fn archive_mod(path: &str, mod_: &InstalledMod) -> Result<String, Box<dyn std::error::Error>> {
+    let xdg_data_home = xdg_dir("XDG_DATA_HOME", ".local/share")?;
+    let folder = xdg_data_home + "/bonelab-mod-manager/archive";
+    fs::create_dir_all(&folder)?;
+    let archive = format!("{}/{}.zip", folder, mod_.barcode());
+    let output = Command::new("zip")
+        .current_dir(path)
+        .arg("-r")
+        .arg(&archive)
+        .arg(mod_.barcode())
+        .arg(mod_.barcode().to_string() + ".manifest")
+        .output()?;
+    if !output.status.success() {
+        throw(&format!("Failed to archive {}", mod_.barcode()))?;
+    }
+    Ok(archive)
+}
+
+/// where mod logos are cached
+pub fn thumbnail_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let xdg_cache_home = xdg_dir("XDG_CACHE_HOME", ".cache")?;
+    Ok(PathBuf::from(
+        xdg_cache_home + "/bonelab-mod-manager/thumbnails",
+    ))
+}
+
+/// the cached logo of a mod, if it was downloaded
+pub fn cached_thumbnail(mod_id: u64) -> Option<PathBuf> {
+    let prefix = format!("{}.", mod_id);
+    fs::read_dir(thumbnail_dir().ok()?)
+        .ok()?
+        .filter_map(|x| x.ok())
+        .find(|x| x.file_name().to_string_lossy().starts_with(&prefix))
+        .map(|x| x.path())
+}
+
+/// download the logo of a mod into the thumbnail cache unless it is already there
+pub async fn cache_thumbnail(
+    mod_id: u64,
+    url: &str,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if let Some(x) = cached_thumbnail(mod_id) {
+        return Ok(x);
+    }
+    let extension = match url.rsplit_once('.') {
+        Some((_, x)) if x.len() <= 4 && !x.contains('/') => x,
+        _ => "png",
+    };
+    let response = reqwest::get(url).await?.error_for_status()?;
+    let bytes = response.bytes().await?;
+    let dir = thumbnail_dir()?;
+    fs::create_dir_all(&dir)?;
+    let file = dir.join(format!("{}.{}", mod_id, extension));
+    fs::write(&file, bytes)?;
+    Ok(file)
+}
+
+/// logos to cache and manifests whose thumbnail override changes, see `plan_thumbnails`
+pub struct Thumbnails {
+    /// the logos to download and manifests to write
+    pub plan: Plan,
+    /// mod ids and logo urls that are not cached yet
+    pub missing: Vec<(u64, String)>,
+}
+
+/// find the logos that are not cached and the manifests `set_thumbnail_overrides` would change
+///
+/// overrides are refused for android, the game on a quest cannot read files on this pc
+pub fn plan_thumbnails(
+    installed_mods: &[InstalledMod],
+    set_override: bool,
+    clear_override: bool,
+    platform: Platform,
+) -> Result<Thumbnails, Box<dyn std::error::Error>> {
+    if set_override && platform == Platform::Android {
+        throw("--override points the game at a file on this pc, which a quest cannot read")?;
+    }
+    let mut thumbnails = Thumbnails {
+        plan: Plan::default(),
+        missing: Vec::new(),
+    };
+    for mod_ in installed_mods {
+        let target = mod_.target();
+        let url = mod_
+            .manifest
+            .mod_listing()
+            .and_then(|x| x.thumbnailUrl.clone());
+        if cached_thumbnail(target.modId).is_none()
+            && let Some(url) = url
+        {
+            thumbnails
+                .plan
+                .actions
+                .push(Action::CacheThumbnail(mod_.barcode().to_string()));
+            thumbnails.missing.push((target.modId, url));
+        }
+        let current = &target.thumbnailOverride;
+        if (set_override && current.is_none()) || (clear_override && current.is_some()) {
+            thumbnails
+                .plan
+                .actions
+                .push(Action::WriteManifest(mod_.barcode().to_string()));
+        }
+    }
+    Ok(thumbnails)
+}
+
+/// point `thumbnailOverride` at the cached logos, or clear it to go back to mod.io
+pub fn set_thumbnail_overrides(
+    path: &str,
+    installed_mods: &[InstalledMod],
+    set_override: bool,
+    clear_override: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for mod_ in installed_mods {
+        let mut manifest = mod_.manifest.clone();
+        let target = match manifest.mod_target_mut(mod_.platform) {
+            Some(x) => x,
+            None => continue,
+        };
+        let thumbnail = match (set_override, clear_override) {
+            (true, _) if target.thumbnailOverride.is_none() => {
+                match cached_thumbnail(target.modId) {
+                    // the game runs under wine, which mounts / as Z:
+                    Some(x) => Some(format!("Z:{}", x.display())),
+                    None => continue,
+                }
+            }
+            (_, true) if target.thumbnailOverride.is_some() => None,
+            _ => continue,
+        };
+        target.thumbnailOverride = thumbnail;
+        save_manifest(PathBuf::from(path), &manifest)?;
+    }
+    Ok(())
+}
+
+/// a mod to install, and the mod that pulled it in if it is a dependency
+pub struct PlannedInstall {
+    /// the mod on mod.io
+    pub mod_: Mod,
+    /// the live modfile for the platform, none when the mod has none
+    pub modfile: Option<modio::files::File>,
+    /// mod ids of its direct dependencies
+    pub dependencies: Vec<u64>,
+    /// name of the mod that depends on it
+    pub required_by: Option<String>,
+}
+
+/// the installs `plan_installs` found and the dependencies it could not resolve
+#[derive(Default)]
+pub struct InstallPlan {
+    /// mods to install, dependencies first
+    pub installs: Vec<PlannedInstall>,
+    /// ids of dependencies mod.io does not serve, the name of the mod needing each, and why
+    pub unavailable: Vec<(u64, String, String)>,
+    /// names along every dependency cycle, see `DependencyOrder`
+    pub cycles: Vec<Vec<String>>,
+}
+
+/// find the dependencies of mods recursively and order the installs so dependencies go first,
+/// each with the live modfile for the platform
+pub async fn plan_installs(
+    modio: &Modio,
+    mods: Vec<Mod>,
+    installed_mods: &[InstalledMod],
+    platform: Platform,
+) -> Result<InstallPlan, Box<dyn std::error::Error>> {
+    let roots: Vec<u64> = mods.iter().map(|x| x.id.get()).collect();
+    let mut queued: HashSet<u64> = roots.iter().copied().collect();
+    let mut graph: HashMap<u64, (Mod, Vec<u64>)> = HashMap::new();
+    let mut unavailable = Vec::new();
+    let mut queue = mods;
+    while let Some(mod_) = queue.pop() {
+        let mut dependencies = Vec::new();
+        if mod_.dependencies {
+            let modref = modio.mod_(Id::new(BONELAB), mod_.id);
+            for dependency in modref.dependencies().list().await? {
+                let dep_id = dependency.mod_id.get();
+                dependencies.push(dep_id);
+                if queued.contains(&dep_id)
+                    || installed_mods.iter().any(|x| x.mod_id() == Some(dep_id))
+                {
+                    continue;
+                }
+                queued.insert(dep_id);
+                match modio.mod_(Id::new(BONELAB), dependency.mod_id).get().await {
+                    Ok(x) => queue.push(x),
+                    Err(x) => unavailable.push((dep_id, mod_.name.clone(), x.to_string())),
+                }
+            }
+        }
+        graph.insert(mod_.id.get(), (mod_, dependencies));
+    }
+
+    let names = graph
+        .iter()
+        .map(|(id, (mod_, dependencies))| (*id, (mod_.name.clone(), dependencies.clone())))
+        .collect();
+    let ordered = order_dependencies(&roots, &names);
+    let mut installs = Vec::new();
+    for (id, required_by) in ordered.order {
+        let (mut mod_, dependencies) = graph.remove(&id).unwrap();
+        let modfile = take_live_modfile(modio, &mut mod_, platform).await?;
+        installs.push(PlannedInstall {
+            mod_,
+            modfile,
+            dependencies,
+            required_by,
+        });
+    }
+    Ok(InstallPlan {
+        installs,
+        unavailable,
+        cycles: ordered.cycles,
+    })
+}
+
+/// the downloads and new folders of planned installs
+pub fn install_plan(installs: &[PlannedInstall]) -> Plan {
+    let mut plan = Plan::default();
+    for planned in installs {
+        let name = match &planned.required_by {
+            Some(x) => format!("{} (dependency of {})", planned.mod_.name, x),
+            None => planned.mod_.name.clone(),
+        };
+        let size = planned.modfile.as_ref().map_or(0, |x| x.filesize);
+        let unpacked = planned
+            .modfile
+            .as_ref()
+            .map_or(0, |x| x.filesize_uncompressed);
+        plan.install(&name, size, unpacked, None);
+    }
+    plan
+}
+
+/// what `install_planned` did
+pub struct InstallOutcome {
+    /// ids of the mods that were installed
+    pub installed: Vec<u64>,
+    /// names of the mods that failed, and why
+    pub failed: Vec<(String, String)>,
+}
+
+/// install planned mods in order and remember their dependencies,
+/// a failed install does not stop the ones after it
+pub async fn install_planned(
+    modio: &Modio,
+    path: &str,
+    plan: Vec<PlannedInstall>,
+    state: &mut State,
+    platform: Platform,
+    pb: &ProgressBar,
+) -> InstallOutcome {
+    let mut outcome = InstallOutcome {
+        installed: Vec::new(),
+        failed: Vec::new(),
+    };
+    pb.set_length(plan.len() as u64);
+    for planned in plan {
+        pb.inc(1);
+        pb.set_message(planned.mod_.name.clone());
+        let installed = download_mod(
+            &planned.mod_,
+            modio,
+            Path::new(path),
+            platform,
+            DownloadOptions {
+                modfile: planned.modfile.as_ref(),
+                ..Default::default()
+            },
+        )
+        .await;
+        match installed {
+            Ok(_) => {
+                outcome.installed.push(planned.mod_.id.get());
+                state
+                    .dependencies
+                    .insert(planned.mod_.id.get(), planned.dependencies);
+            }
+            Err(x) => outcome.failed.push((planned.mod_.name, x.to_string())),
+        }
+    }
+    pb.finish_and_clear();
+    outcome
+}
+
+/// a pallet zip or folder unpacked next to the mods folder, see `stage_local`
+pub struct LocalInstall {
+    /// the unpacked pallet
+    pub staged: Staged,
+    /// the manifest of the copy it replaces
+    pub old: Option<Manifest>,
+    /// the folder and manifest it creates or replaces
+    pub plan: Plan,
+}
+
+/// unpack a pallet zip or folder that is not on mod.io and find what it replaces
+///
+/// the staging folder is removed again when the manifest of the installed copy cannot be read
+pub fn stage_local(
+    source: &Path,
+    is_dir: bool,
+    path: &str,
+) -> Result<LocalInstall, Box<dyn std::error::Error>> {
+    let name = match source.file_stem() {
+        Some(x) => x.to_string_lossy().into_owned() + ".staging",
+        None => throw("Not a file or folder")?,
+    };
+    let staged = match is_dir {
+        true => stage_dir(source, path, &name)?,
+        false => stage_archive(&source.to_string_lossy(), path, &name)?,
+    };
+    let old = PathBuf::from(path).join(staged.barcode.clone() + ".manifest");
+    let old = match fs::read_to_string(&old) {
+        Ok(x) => match Manifest::parse(&x) {
+            Ok(x) => Some(x),
+            Err(e) => {
+                fs::remove_dir_all(&staged.folder)?;
+                return throw(&format!(
+                    "could not read {}, not replacing it: {}",
+                    old.display(),
+                    e
+                ));
+            }
+        },
+        Err(_) => None,
+    };
+    let mut plan = Plan::default();
+    match &old {
+        Some(_) => plan
+            .actions
+            .push(Action::ReplaceFolder(staged.barcode.clone())),
+        None => plan
+            .actions
+            .push(Action::CreateFolder(staged.barcode.clone())),
+    }
+    plan.actions
+        .push(Action::WriteManifest(staged.barcode.clone()));
+    Ok(LocalInstall { staged, old, plan })
+}
+
+/// move a staged local pallet into the mods folder with a manifest marking it local
+pub fn install_local(
+    local: &LocalInstall,
+    path: &str,
+    platform: Platform,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let staged = &local.staged;
+    let pallet = read_pallet_json(&PathBuf::from(&staged.folder).join(&staged.barcode))?;
+    let mut manifest = make_local_manifest(staged, pallet, platform);
+    if let Some(old) = &local.old {
+        manifest.pallet_mut().installedDate = old.pallet().installedDate.clone();
+        manifest.pallet_mut().active = old.pallet().active;
+        manifest.pallet_mut().extra = old.pallet().extra.clone();
+        manifest.extra = old.extra.clone();
+    }
+    install_staged(Path::new(path), staged, &manifest)
+}
+
+/// mods in install order, from `order_dependencies`
+pub struct DependencyOrder {
+    /// mod ids, each with the name of the mod that pulled it in as a dependency
+    pub order: Vec<(u64, Option<String>)>,
+    /// names along every dependency cycle, the dependency closing a cycle is left out
+    pub cycles: Vec<Vec<String>>,
+}
+
+/// order mods so every mod comes after its dependencies
+///
+/// `graph` has the name and dependencies of each mod, dependencies that are not in it are
+/// already installed
+pub fn order_dependencies(
+    roots: &[u64],
+    graph: &HashMap<u64, (String, Vec<u64>)>,
+) -> DependencyOrder {
+    let mut order = Vec::new();
+    let mut cycles = Vec::new();
+    let mut done = HashSet::new();
+    for root in roots {
+        visit(
+            *root,
+            None,
+            graph,
+            &mut Vec::new(),
+            &mut done,
+            &mut order,
+            &mut cycles,
+        );
+    }
+    DependencyOrder { order, cycles }
+}
+
+/// depth first walk of the dependency graph, each mod is pushed after its dependencies
+fn visit(
+    id: u64,
+    required_by: Option<String>,
+    graph: &HashMap<u64, (String, Vec<u64>)>,
+    stack: &mut Vec<u64>,
+    done: &mut HashSet<u64>,
+    order: &mut Vec<(u64, Option<String>)>,
+    cycles: &mut Vec<Vec<String>>,
+) {
+    if done.contains(&id) || !graph.contains_key(&id) {
+        return;
+    }
+    if let Some(start) = stack.iter().position(|x| *x == id) {
+        let cycle = stack[start..]
+            .iter()
+            .chain([&id])
+            .map(|x| graph[x].0.clone())
+            .collect();
+        cycles.push(cycle);
+        return;
+    }
+    stack.push(id);
+    let (name, dependencies) = &graph[&id];
+    for dependency in dependencies {
+        visit(
+            *dependency,
+            Some(name.clone()),
+            graph,
+            stack,
+            done,
+            order,
+            cycles,
+        );
+    }
+    stack.pop();
+    done.insert(id);
+    order.push((id, required_by));
+}
+
+/// files a pallet build needs, everything else in the folder is left out of the zip
+fn is_pallet_file(name: &str) -> bool {
+    name.ends_with(".pallet.json")
+        || (name.starts_with("catalog_") && (name.ends_with(".json") || name.ends_with(".hash")))
+        || name.ends_with(".bundle")
+}
+
+/// paths of the pallet files under a folder, relative to it
+fn pallet_build_files(
+    folder: &Path,
+    prefix: &str,
+    files: &mut Vec<String>,
+    stray: &mut Vec<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for entry in fs::read_dir(folder)? {
+        let entry = entry?;
+        let name = prefix.to_string() + &entry.file_name().to_string_lossy();
+        if entry.file_type()?.is_dir() {
+            pallet_build_files(&entry.path(), &(name + "/"), files, stray)?;
+        } else if is_pallet_file(&name) {
+            files.push(name);
+        } else {
+            stray.push(name);
+        }
+    }
+    Ok(())
+}
+
+/// a checked pallet build folder and the files `pack` puts in the zip
+pub struct PalletBuild {
+    /// the folder named after the barcode
+    pub folder: PathBuf,
+    /// barcode from the pallet json
+    pub barcode: String,
+    /// paths relative to the folder, sorted
+    pub files: Vec<String>,
+    /// other files in the folder, sorted, they are left out of the zip
+    pub left_out: Vec<String>,
+    /// files a pallet usually has that the folder is missing
+    pub missing: Vec<&'static str>,
+}
+
+/// check a pallet build folder and list the files that go into its zip
+pub fn pallet_build(folder: &Path) -> Result<PalletBuild, Box<dyn std::error::Error>> {
+    let folder = folder.canonicalize()?;
+    let barcode = match folder.file_name() {
+        Some(x) => x.to_string_lossy().into_owned(),
+        None => throw("Not a pallet folder")?,
+    };
+    let pallet = match read_pallet_json(&folder)? {
+        Some(x) => x,
+        None => throw(&format!("{} has no pallet json", barcode))?,
+    };
+    match pallet.barcode() {
+        Some(x) if x == barcode => {}
+        Some(x) => throw(&format!(
+            "the folder is named {} but the pallet json has barcode {}",
+            barcode, x
+        ))?,
+        None => throw("the pallet json has no barcode")?,
+    }
+
+    let mut files = Vec::new();
+    let mut stray = Vec::new();
+    pallet_build_files(&folder, "", &mut files, &mut stray)?;
+    if !files
+        .iter()
+        .any(|x| x.starts_with("catalog_") && x.ends_with(".json"))
+    {
+        throw(&format!("{} has no catalog json", barcode))?;
+    }
+    let mut missing = Vec::new();
+    if !files
+        .iter()
+        .any(|x| x.starts_with("catalog_") && x.ends_with(".hash"))
+    {
+        missing.push("catalog hash");
+    }
+    if !files.iter().any(|x| x.ends_with(".bundle")) {
+        missing.push("bundles");
+    }
+    stray.sort();
+    files.sort();
+    Ok(PalletBuild {
+        folder,
+        barcode,
+        files,
+        left_out: stray,
+        missing,
+    })
+}
+
+/// zip a pallet build checked by `pallet_build` deterministically, returns the md5 of the zip
+pub fn pack(build: &PalletBuild, output: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let PalletBuild {
+        folder,
+        barcode,
+        files,
+        ..
+    } = build;
+
+    // copy into a staging folder so times and modes can be fixed without touching the build
+    let staging = env::temp_dir().join(format!("bonelab-mod-manager-pack-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&staging);
+    let mut entries = Vec::new();
+    for file in files {
+        let to = staging.join(barcode).join(file);
+        fs::create_dir_all(to.parent().unwrap())?;
+        fs::copy(folder.join(file), &to)?;
+        entries.push(format!("{}/{}", barcode, file));
+    }
+    let output = std::path::absolute(output)?;
+    let _ = fs::remove_file(&output);
+    let status = Command::new("sh")
+        .current_dir(&staging)
+        .env("TZ", "UTC")
+        .arg("-c")
+        .arg("chmod -R u=rwX,go=rX . && find . -exec touch -h -d 1980-01-01T00:00:00 {} + && zip -q -X -D \"$0\" -@")
+        .arg(&output)
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            child.stdin.take().unwrap().write_all(entries.join("\n").as_bytes())?;
+            child.wait()
+        })?;
+    fs::remove_dir_all(&staging)?;
+    if !status.success() {
+        throw(&format!("Failed to zip {}", barcode))?;
+    }
+    md5_file(&output.to_string_lossy())
+}
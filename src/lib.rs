@@ -0,0 +1,72 @@
+//! library behind the bonelab mod manager cli
+//!
+//! - [`structs`] and [`manifest`] read and write the `.manifest` files bonelab keeps next to each pallet
+//! - [`client`] logs in to mod.io and wraps the requests the mod manager makes
+//! - [`installer`] downloads, unpacks, installs and removes pallets
+//! - [`state`] is the local state store, [`plan`] describes changes before they are made
+
+#![warn(missing_docs)]
+
+pub mod adopt;
+pub mod client;
+pub mod installer;
+pub mod manifest;
+pub mod plan;
+pub mod profile;
+pub mod publish;
+pub mod state;
+pub mod structs;
+pub mod sync;
+pub mod updater;
+
+/// an error with a message, used for everything the mod manager reports itself
+#[derive(Debug)]
+pub struct BMMError(pub String);
+
+impl std::fmt::Display for BMMError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for BMMError {}
+
+/// return an error with a message
+pub fn throw<T>(err: &str) -> Result<T, Box<dyn std::error::Error>> {
+    Err(Box::new(BMMError(err.into())))
+}
+
+/// an xdg base directory, `fallback` under `$HOME` when `var` is not set
+pub fn xdg_dir(var: &str, fallback: &str) -> Result<String, Box<dyn std::error::Error>> {
+    match std::env::var(var) {
+        Ok(x) => Ok(x),
+        Err(_) => match std::env::var("HOME") {
+            Ok(home) => Ok(format!("{}/{}", home, fallback)),
+            Err(_) => throw(&format!("neither {} nor HOME is set", var)),
+        },
+    }
+}
+
+/// format a byte count as MiB or GiB
+pub fn format_size(bytes: u64) -> String {
+    if bytes >= 1024 * 1024 * 1024 {
+        format!("{:.1} GiB", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+    } else {
+        format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
+
+/// format unix seconds as a yyyy-mm-dd date
+pub fn format_date(secs: i64) -> String {
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = secs.div_euclid(86400) + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
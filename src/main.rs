@@ -1,36 +1,46 @@
 use std::{
-    collections::HashMap,
     env,
     fs::{self, File},
     io::{self, Write},
-    path::PathBuf,
-    process::Command,
-    thread,
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    path::{Path, PathBuf},
+    time::Duration,
 };
 
 use indicatif::{ProgressBar, ProgressStyle};
-use modio::{
-    Credentials, DownloadAction, Modio, Result, TargetPlatform, auth::Token, mods::filters::GameId,
-    types::id::Id,
-};
-use modio::{files::filters::Id as fid, mods::Mod};
-use modio::{filter::prelude::*, types::Timestamp};
-use reqwest::Client;
-use serde_json::{Value, json};
+use modio::filter::prelude::*;
+use modio::{Modio, files::filters::Id as fid, mods::Mod, types::id::Id};
 use structopt::StructOpt;
 
-use crate::structs::{Isa, Manifest, ModListing, ModTarget, Object, Pallet, Reference, Root};
+use bonelab_mod_manager::adopt::{Skipped, adoptable, plan_adoption};
+use bonelab_mod_manager::client::{BONELAB, live_modfile_id, search_pallet, subscribed_mods};
+use bonelab_mod_manager::installer::{
+    self, DownloadOptions, InstallPlan, PlannedInstall, archive_mod, cache_thumbnail, download_mod,
+    install_plan, pack, pallet_build, plan_installs, plan_thumbnails, remove_mod,
+    set_thumbnail_overrides, stage_local,
+};
+use bonelab_mod_manager::manifest::{
+    InstalledMod, missing_dependencies, read_installed_mods, save_manifest,
+};
+use bonelab_mod_manager::plan::{Action, Plan};
+use bonelab_mod_manager::profile::{apply_activation, fetch_profile_mods, plan_activation};
+use bonelab_mod_manager::publish::{
+    self, Details, Upload, apply_edit, plan_details_edit, plan_logo_edit, plan_tags_edit,
+    prepare_upload,
+};
+use bonelab_mod_manager::state::{State, Unavailable};
+use bonelab_mod_manager::structs::Platform;
+use bonelab_mod_manager::sync::{
+    Direction, ModInstall, apply_subscription_sync, apply_sync, install_modfile, modlist,
+    plan_import, plan_subscription_sync, plan_sync, read_desired, read_lockfile, read_modlist,
+    resolve_lock, write_lockfile, write_modlist,
+};
+use bonelab_mod_manager::updater::{
+    self, PendingUpdate, UpdateCheck, changelogs, install_update, rollback_target, update_plan,
+};
+use bonelab_mod_manager::{format_date, format_size, throw, xdg_dir};
 
-const BONELAB: u64 = 3809;
 const TEMPLATE: &str = "[{bar}][time: {elapsed_precise}][eta: {eta_precise}] {msg}";
 
-#[derive(Clone)]
-struct InstalledMod {
-    path: String,
-    manifest: Manifest,
-}
-
 /// Bonelab mod manager
 #[derive(structopt::StructOpt)]
 struct Opt {
@@ -44,38 +54,229 @@ struct Opt {
     /// usually something like /C:/users/steamuser/AppData/LocalLow/Stress Level Zero/BONELAB/Mods/
     #[structopt(short, long)]
     mod_folder: Option<PathBuf>,
+    /// platform of the mods folder, pc or android for a quest
+    #[structopt(long, default_value = "pc")]
+    platform: Platform,
     /// subscribe to all mods
     #[structopt(short, long, name = "subscribe to all mods")]
     subscribe_all: bool,
-    /// update all mods / does not do anything rn
+    /// update all mods
     #[structopt(short, long, name = "update all mods")]
     update_all: bool,
     #[structopt(short, long, name = "install subscribed mods")]
     install_all_subscribed: bool,
+    /// check every mod for updates instead of only the mods with new mod.io events
+    #[structopt(long)]
+    full_scan: bool,
+    /// answer yes to every confirmation
+    #[structopt(short, long, global = true)]
+    yes: bool,
+    /// print what would be changed without changing anything
+    #[structopt(short = "n", long, global = true)]
+    dry_run: bool,
+    #[structopt(subcommand)]
+    cmd: Option<Cmd>,
 }
 
-mod structs;
+#[derive(structopt::StructOpt)]
+enum Cmd {
+    /// manage named sets of mods
+    Profile(ProfileCmd),
+    /// write the installed mods and their exact versions to a modlist file
+    Export { file: PathBuf },
+    /// install the exact mod versions listed in a modlist file
+    Import { file: PathBuf },
+    /// make the mods folder match a desired mods file, pinning exact versions in a lockfile
+    Sync {
+        /// json file listing mod ids and optional semver version requirements
+        file: PathBuf,
+        /// defaults to the desired mods file with a .lock extension
+        #[structopt(long)]
+        lockfile: Option<PathBuf>,
+        /// fail instead of changing the lockfile
+        #[structopt(long)]
+        locked: bool,
+        /// resolve every mod again instead of keeping versions that are already locked
+        #[structopt(long)]
+        update: bool,
+    },
+    /// hold mods at their installed modfile so updates skip them, lists pins if no mods are given
+    Pin { mods: Vec<String> },
+    /// let updates change pinned mods again
+    Unpin { mods: Vec<String> },
+    /// list mods with updates available and the changelogs of every newer modfile
+    Outdated,
+    /// update all mods, same as -u
+    Update,
+    /// rewrite the title, description, author and version in manifests from mod.io
+    RefreshMetadata,
+    /// download the logos of installed mods so thumbnails show without the network
+    Thumbnails {
+        /// point thumbnailOverride in each manifest at the downloaded logo
+        #[structopt(long = "override")]
+        set_override: bool,
+        /// go back to the mod.io thumbnails
+        #[structopt(long, conflicts_with = "set-override")]
+        clear_override: bool,
+    },
+    /// install mods by mod.io id, along with their dependencies
+    Install {
+        #[structopt(conflicts_with_all = &["from-file", "from-dir"])]
+        mods: Vec<String>,
+        /// install a pallet zip that is not on mod.io
+        #[structopt(long, conflicts_with = "from-dir")]
+        from_file: Option<PathBuf>,
+        /// install a pallet folder that is not on mod.io
+        #[structopt(long)]
+        from_dir: Option<PathBuf>,
+    },
+    /// uninstall mods by id or barcode
+    Remove { mods: Vec<String> },
+    /// deal with installed mods that were deleted or hidden on mod.io
+    Unavailable(UnavailableCmd),
+    /// find installed pallets that depend on pallets missing from the mods folder
+    Doctor {
+        /// do not search mod.io for the missing pallets
+        #[structopt(long)]
+        offline: bool,
+    },
+    /// find pallets the mod manager does not track and match them to mod.io mods
+    Adopt {
+        /// only adopt this pallet
+        barcode: Option<String>,
+        /// the mod.io mod id to adopt the pallet as, instead of searching
+        #[structopt(long, requires = "barcode")]
+        mod_id: Option<u64>,
+    },
+    /// manage a mod you maintain on mod.io
+    Mod(ModCmd),
+    /// browse, install, update and remove mods in a full-screen interface
+    Tui,
+    /// zip a pallet build folder the way mod.io downloads are laid out
+    Pack {
+        /// the barcode folder of a pallet build
+        folder: PathBuf,
+        /// defaults to <barcode>.zip in the current folder
+        #[structopt(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// upload a pallet folder or zip as a new modfile of a mod
+    Publish {
+        /// pallet folder or zip
+        source: PathBuf,
+        #[structopt(long)]
+        mod_id: u64,
+        #[structopt(long)]
+        version: String,
+        #[structopt(long)]
+        changelog: Option<String>,
+        /// pc or android, can be given more than once, defaults to pc
+        #[structopt(long = "platform")]
+        platforms: Vec<Platform>,
+        /// make the new modfile the live one
+        #[structopt(long)]
+        live: bool,
+    },
+    /// make mod.io subscriptions and the mods folder match each other
+    SyncSubscriptions {
+        /// local-to-remote, remote-to-local or mirror
+        #[structopt(long, default_value = "mirror")]
+        direction: Direction,
+    },
+    /// list every modfile of a mod on mod.io
+    Versions {
+        #[structopt(name = "mod")]
+        mod_: String,
+    },
+    /// reinstall an older modfile of a mod
+    Rollback {
+        #[structopt(name = "mod")]
+        mod_: String,
+        /// modfile id or version string, defaults to the modfile before the installed one
+        #[structopt(long)]
+        to: Option<String>,
+    },
+}
 
-#[derive(Debug)]
-struct BMMError(String);
-impl std::fmt::Display for BMMError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", self.0)
-    }
+#[derive(structopt::StructOpt)]
+enum UnavailableCmd {
+    /// list mods that mod.io does not serve anymore
+    List,
+    /// keep the local copy and stop reporting it
+    Keep {
+        #[structopt(name = "mod")]
+        mod_: String,
+    },
+    /// zip the mod into ~/.local/share/bonelab-mod-manager/archive and remove it
+    Archive {
+        #[structopt(name = "mod")]
+        mod_: String,
+    },
+    /// delete the mod
+    Remove {
+        #[structopt(name = "mod")]
+        mod_: String,
+    },
 }
-impl std::error::Error for BMMError {}
 
-fn throw<T>(err: &str) -> Result<T, Box<dyn std::error::Error>> {
-    Err(Box::new(BMMError(err.into())))
+#[derive(structopt::StructOpt)]
+enum ModCmd {
+    /// change a mod's details, tags or logo
+    Edit(EditCmd),
 }
 
+#[derive(structopt::StructOpt)]
+enum EditCmd {
+    /// change the name, summary, description or visibility
+    Details {
+        mod_id: u64,
+        #[structopt(long)]
+        name: Option<String>,
+        #[structopt(long)]
+        summary: Option<String>,
+        /// file holding the new description, html is allowed
+        #[structopt(long)]
+        description_file: Option<PathBuf>,
+        /// public or hidden
+        #[structopt(long, possible_values = &["public", "hidden"])]
+        visibility: Option<String>,
+    },
+    /// add or remove tags
+    Tags {
+        mod_id: u64,
+        #[structopt(long)]
+        add: Vec<String>,
+        #[structopt(long)]
+        remove: Vec<String>,
+    },
+    /// upload a new logo image
+    Logo { mod_id: u64, file: PathBuf },
+}
+
+#[derive(structopt::StructOpt)]
+enum ProfileCmd {
+    /// list saved profiles
+    List,
+    /// show the mods in a profile
+    Show { name: String },
+    /// save a profile from mod ids or barcodes, defaults to the currently active mods
+    Save { name: String, mods: Vec<String> },
+    /// delete a profile
+    Delete { name: String },
+    /// make the mods folder match a profile
+    Activate {
+        name: String,
+        /// uninstall mods that are not in the profile instead of disabling them
+        #[structopt(long)]
+        uninstall: bool,
+    },
+}
+
+mod tui;
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let xdg_config_home = env::var("XDG_CONFIG_HOME").unwrap_or_else(|_| {
-        // Default to ~/.config if XDG_CONFIG_HOME is not set
-        let home = env::var("HOME").unwrap();
-        format!("{}/.config", home)
-    });
+    let xdg_config_home = xdg_dir("XDG_CONFIG_HOME", ".config")?;
     let opt = Opt::from_args();
     let path = opt.mod_folder.clone();
     let path = match path {
@@ -91,105 +292,231 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     println!("{}", path);
 
-    let mut files = Vec::new();
-    match fs::read_dir(&path) {
-        Ok(entries) => {
-            for entry in entries {
-                match entry {
-                    Ok(entry) => {
-                        let file_name = entry.file_name();
-                        // println!("{}", file_name.to_string_lossy());
-                        files.push(file_name.to_string_lossy().into_owned());
+    println!("Reading mods...");
+    let pb = ProgressBar::new(0);
+    let installed = read_installed_mods(&path, opt.platform, &pb)?;
+    pb.finish_and_clear();
+    for (manifest, x) in &installed.unreadable {
+        eprintln!("! could not read {}: {}", manifest, x);
+    }
+    for (barcode, other) in &installed.other_platform {
+        eprintln!(
+            "! {} was installed for {}, not {}",
+            barcode, other, opt.platform
+        );
+    }
+    let installed_mods = installed.mods;
+    let local_mods = installed.local;
+
+    let mut state = State::load(&xdg_config_home)?;
+    if let Some(cmd) = &opt.cmd {
+        match cmd {
+            Cmd::Profile(cmd) => {
+                profile(
+                    cmd,
+                    &opt,
+                    &xdg_config_home,
+                    &path,
+                    &installed_mods,
+                    &local_mods,
+                    &mut state,
+                )
+                .await?
+            }
+            Cmd::Export { file } => export(file, &installed_mods, opt.dry_run)?,
+            Cmd::Import { file } => {
+                let modio = login(&opt, &xdg_config_home).await?;
+                import(file, &modio, &path, &installed_mods, &opt).await?;
+                state.rescan();
+            }
+            Cmd::Sync {
+                file,
+                lockfile,
+                locked,
+                update,
+            } => {
+                let modio = login(&opt, &xdg_config_home).await?;
+                let lockfile = match lockfile {
+                    Some(x) => x.clone(),
+                    None => file.with_extension("lock"),
+                };
+                sync(
+                    file,
+                    &lockfile,
+                    *locked,
+                    *update,
+                    &modio,
+                    &path,
+                    &installed_mods,
+                    &mut state,
+                    &opt,
+                )
+                .await?;
+                state.rescan();
+            }
+            Cmd::Pin { mods } => pin(mods, &installed_mods, &mut state)?,
+            Cmd::Unpin { mods } => {
+                for key in mods {
+                    let mod_id = mod_id_of(key, &installed_mods)?;
+                    if state.pins.remove(&mod_id).is_none() {
+                        println!("{} is not pinned", key);
                     }
-                    Err(e) => eprintln!("Error reading entry: {}", e),
                 }
+                // held back updates have no new events, so they would not be found
+                state.rescan();
             }
-        }
-        Err(e) => eprintln!("Error reading directory: {}", e),
-    }
-
-    let mod_manifests: Vec<String> = files
-        .iter()
-        .filter(|x| x.ends_with(".manifest") && !x.starts_with("SLZ"))
-        .map(|x| x.clone())
-        .collect();
-
-    let mut installed_mods = Vec::new();
-
-    println!("Reading mods...");
-    let pb = ProgressBar::new(mod_manifests.len() as u64);
-    for manifest in mod_manifests {
-        // println!("{}", manifest);
-        let path = path.clone() + &manifest;
-        let manifest = fs::read_to_string(&path)?;
-        let manifest: Manifest = serde_json::from_str(&manifest)?;
-        let _target = match &manifest.objects.mod_target {
-            Some(x) => x,
-            None => {
-                continue;
+            Cmd::Versions { mod_ } => {
+                let modio = login(&opt, &xdg_config_home).await?;
+                versions(mod_, &modio, &installed_mods, opt.platform).await?
             }
-        };
-        installed_mods.push(InstalledMod { path, manifest });
-        pb.inc(1);
-    }
-    pb.finish_and_clear();
-
-    let mut modio = Modio::new(Credentials::new(match opt.api_key {
-        Some(x) => x,
-        None => {
-            match fs::read_to_string(xdg_config_home.clone() + "/bonelab-mod-manager/modio_api_key")
-            {
-                Ok(x) => x,
-                Err(_) => throw("Missing modio api key")?,
+            Cmd::Rollback { mod_, to } => {
+                let modio = login(&opt, &xdg_config_home).await?;
+                rollback(mod_, to, &modio, &path, &installed_mods, &state, &opt).await?;
+                state.rescan();
             }
-        }
-    }))?;
-
-    let access_token =
-        fs::read_to_string(xdg_config_home.clone() + "/bonelab-mod-manager/modio_access_token");
-    if let Ok(token) = access_token {
-        println!("token found");
-        let token = Token {
-            value: token,
-            expired_at: None,
-        };
-        modio = modio.with_token(token);
-    } else {
-        modio
-            .auth()
-            .request_code(match &opt.email {
-                Some(x) => x,
-                None => {
-                    panic!("No email found")
+            Cmd::Outdated => {
+                let modio = login(&opt, &xdg_config_home).await?;
+                outdated(&modio, &installed_mods, &mut state).await?
+            }
+            Cmd::Update => {
+                let modio = login(&opt, &xdg_config_home).await?;
+                update_all(&modio, &path, &installed_mods, &mut state, &opt).await?
+            }
+            Cmd::Install {
+                from_file: Some(file),
+                ..
+            } => install_local(file, false, &path, &opt)?,
+            Cmd::Install {
+                from_dir: Some(dir),
+                ..
+            } => install_local(dir, true, &path, &opt)?,
+            Cmd::Install { mods, .. } => {
+                let modio = login(&opt, &xdg_config_home).await?;
+                install(mods, &modio, &path, &installed_mods, &mut state, &opt).await?
+            }
+            Cmd::Tui => {
+                // every key in the tui acts right away, there is no plan to print
+                if opt.dry_run {
+                    throw("The tui cannot do a dry run")?;
                 }
-            })
-            .await?;
-        let code = prompt("security code: ")?;
-        let creds = modio.auth().security_code(&code).await?;
-        if let Some(token) = &creds.token {
-            println!("Access token:\n{}", token.value);
-            let mut file =
-                File::create(xdg_config_home.clone() + "/bonelab-mod-manager/modio_access_token")?;
-            file.write_all(token.value.as_bytes())?;
-            modio = modio.with_token(token.clone());
-        } else {
-            panic!("could not login");
+                let modio = login(&opt, &xdg_config_home).await?;
+                return tui::run(
+                    modio,
+                    path,
+                    opt.platform,
+                    xdg_config_home,
+                    installed_mods,
+                    state,
+                )
+                .await;
+            }
+            Cmd::Mod(ModCmd::Edit(cmd)) => {
+                let modio = login(&opt, &xdg_config_home).await?;
+                edit_mod(cmd, &modio, &opt).await?
+            }
+            Cmd::Pack { folder, output } => {
+                let output = match output {
+                    Some(x) => x.clone(),
+                    None => match folder.canonicalize()?.file_name() {
+                        Some(x) => PathBuf::from(format!("{}.zip", x.to_string_lossy())),
+                        None => throw("Not a pallet folder")?,
+                    },
+                };
+                let build = pallet_build(folder)?;
+                for missing in &build.missing {
+                    println!("! {} has no {}", build.barcode, missing);
+                }
+                for x in &build.left_out {
+                    println!("leaving out {}", x);
+                }
+                if opt.dry_run {
+                    println!(
+                        "would pack {} files of {} into {}",
+                        build.files.len(),
+                        build.barcode,
+                        output.display()
+                    );
+                    println!("dry run, nothing was changed");
+                    return Ok(());
+                }
+                let md5 = pack(&build, &output)?;
+                println!(
+                    "{} ({}), md5 {}",
+                    output.display(),
+                    format_size(fs::metadata(&output)?.len()),
+                    md5
+                );
+            }
+            Cmd::Publish {
+                source,
+                mod_id,
+                version,
+                changelog,
+                platforms,
+                live,
+            } => {
+                let modio = login(&opt, &xdg_config_home).await?;
+                let upload = Upload {
+                    version,
+                    changelog,
+                    platforms,
+                    live: *live,
+                };
+                publish(source, *mod_id, &upload, &modio, &xdg_config_home, &opt).await?
+            }
+            Cmd::RefreshMetadata => {
+                let modio = login(&opt, &xdg_config_home).await?;
+                refresh_metadata(&modio, &path, &installed_mods, &opt).await?
+            }
+            Cmd::Thumbnails {
+                set_override,
+                clear_override,
+            } => thumbnails(*set_override, *clear_override, &path, &installed_mods, &opt).await?,
+            Cmd::SyncSubscriptions { direction } => {
+                let modio = login(&opt, &xdg_config_home).await?;
+                sync_subscriptions(*direction, &modio, &path, &installed_mods, &mut state, &opt)
+                    .await?
+            }
+            Cmd::Remove { mods } => remove(mods, &path, &installed_mods, &mut state, &opt)?,
+            Cmd::Unavailable(cmd) => unavailable(cmd, &path, &installed_mods, &mut state, &opt)?,
+            Cmd::Adopt { barcode, mod_id } => {
+                let modio = login(&opt, &xdg_config_home).await?;
+                adopt(barcode, *mod_id, &modio, &path, &opt).await?
+            }
+            Cmd::Doctor { offline } => {
+                let missing = doctor(&path)?;
+                if !missing.is_empty() && !offline {
+                    let modio = login(&opt, &xdg_config_home).await?;
+                    find_missing_pallets(&missing, &modio).await?;
+                }
+            }
+        }
+        if !opt.dry_run {
+            state.save(&xdg_config_home)?;
         }
+        return Ok(());
     }
-    let user = modio.user().current().await?;
-    println!("logged in as {}", user.unwrap().username);
+
+    let modio = login(&opt, &xdg_config_home).await?;
 
     if opt.subscribe_all {
-        let mut subscribed_mods = match fs::read_to_string(
+        let mut subscribed_mods = fs::read_to_string(
             xdg_config_home.clone() + "/bonelab-mod-manager/modio_subscribed_mods",
-        ) {
-            Ok(x) => x,
-            Err(_) => String::new(),
-        };
+        )
+        .unwrap_or_default();
         let installed_mods: Vec<&InstalledMod> = installed_mods
             .iter()
             .filter(|x| !subscribed_mods.contains(&x.path))
             .collect();
+        let mut plan = Plan::default();
+        for mod_ in &installed_mods {
+            plan.actions
+                .push(Action::Subscribe(mod_.barcode().to_string()));
+        }
+        let installed_mods = match review(&plan, &path, &opt)? {
+            true => installed_mods,
+            false => Vec::new(),
+        };
         println!("subscribing to all installed mods...");
         let pb = ProgressBar::new(installed_mods.len() as u64);
         pb.set_style(ProgressStyle::with_template(TEMPLATE).unwrap());
@@ -199,7 +526,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 "subscribing to {:?}",
                 PathBuf::from(&mod_.path).file_name().unwrap()
             ));
-            let mod_id = match &mod_.manifest.objects.mod_target {
+            let mod_id = match mod_.manifest.mod_target(opt.platform) {
                 Some(x) => x,
                 None => {
                     panic!()
@@ -223,309 +550,1010 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         delay = 2 * delay + 1;
                     }
                 };
-                thread::sleep(Duration::new(delay, 0));
+                tokio::time::sleep(Duration::new(delay, 0)).await;
             }
         }
         pb.finish_and_clear();
-        let mut file = File::create("modio_subscribed_mods")?;
-        file.write_all(subscribed_mods.as_bytes())?;
+        if !opt.dry_run {
+            let mut file = File::create("modio_subscribed_mods")?;
+            file.write_all(subscribed_mods.as_bytes())?;
+        }
     }
     if opt.update_all {
-        println!("updating all installed mods...");
-        let pb = ProgressBar::new(installed_mods.len() as u64);
-        pb.set_style(ProgressStyle::with_template(TEMPLATE).unwrap());
-        // loop through all mods and check if its up to date
-        'baseloop: for mod_ in &installed_mods {
-            pb.inc(1);
-            pb.set_message(format!(
-                "Updating {}",
-                mod_.manifest.clone().objects.pallet.palletBarcode
-            ));
-            let target = match mod_.manifest.clone().objects.mod_target {
-                Some(x) => x,
-                None => {
-                    continue;
-                }
-            };
-            let installed_version: i64 = mod_.manifest.clone().objects.pallet.updateDate.parse()?;
-            let modref = modio.mod_(Id::new(BONELAB), Id::new(target.modId));
-            let mut online_mod = None;
-            let mut delay = 0;
-            while online_mod.is_none() {
-                match modref.clone().get().await {
-                    Ok(x) => online_mod = Some(x),
-                    Err(x) => {
-                        println!("Error: {}", x);
-                        if !x.is_ratelimited() {
-                            println!(
-                                "skipped {}",
-                                mod_.manifest.clone().objects.pallet.palletBarcode
-                            );
-                            continue 'baseloop;
-                        }
-                        delay = 2 * delay + 1;
-                    }
-                }
-                thread::sleep(Duration::new(delay, 0));
-            }
-
-            let online_mod = online_mod.unwrap();
-            let online_version = online_mod.date_updated.as_secs() * 1000;
-
-            if online_version <= installed_version {
-                continue;
-            }
-
-            // find the newest files for the mod
-            let files = modref.files();
-            let filter = fid::asc();
-            let files = files.search(filter).collect().await?;
-            let files: Vec<&modio::files::File> = files
-                .iter()
-                .filter(|file| file.platforms[0].target == TargetPlatform::WINDOWS)
-                .collect();
-
-            let mut new_manifest = mod_.manifest.clone();
-            new_manifest.objects.pallet.updateDate = online_version.to_string();
-            // download
-            download_mod(
-                &online_mod,
-                &modio,
-                path.clone(),
-                PathBuf::from(path.clone()),
-                Some(new_manifest),
-                files.last().map(|v| &**v), // the highest file id is the latest modfile
-                Some(mod_.manifest.objects.pallet.installedDate.parse().unwrap()),
-            )
-            .await?;
-        }
+        update_all(&modio, &path, &installed_mods, &mut state, &opt).await?;
     }
 
     if opt.install_all_subscribed {
         println!("installing all new subscribed mods");
-        let filter = GameId::_in(BONELAB).and(Name::asc());
-        let query = modio.user().subscriptions(filter).collect().await?;
-        let pb = ProgressBar::new(installed_mods.len() as u64);
-        pb.set_style(ProgressStyle::with_template(TEMPLATE).unwrap());
-        for mod_ in query.iter() {
-            pb.inc(1);
-            pb.set_message(format!("{}", mod_.name));
-            let mut found = false;
-            'inner: for i_mod_ in installed_mods.iter() {
-                let target = match i_mod_.manifest.clone().objects.mod_target {
-                    Some(x) => x,
-                    None => continue 'inner,
-                };
-                if mod_.id == target.modId {
-                    found = true;
-                    break 'inner;
-                }
+        let new_mods: Vec<Mod> = subscribed_mods(&modio)
+            .await?
+            .into_iter()
+            .filter(|mod_| {
+                !installed_mods
+                    .iter()
+                    .any(|x| x.mod_id() == Some(mod_.id.get()))
+            })
+            .collect();
+        let planned = plan_installs(&modio, new_mods, &installed_mods, opt.platform).await?;
+        print_install_problems(&planned);
+        let installs = planned.installs;
+        if review(&install_plan(&installs), &path, &opt)? {
+            install_planned(&modio, &path, installs, &mut state, opt.platform).await;
+        }
+    }
+    if !opt.dry_run {
+        state.save(&xdg_config_home)?;
+    }
+    Ok(())
+}
+
+async fn sync_subscriptions(
+    direction: Direction,
+    modio: &Modio,
+    path: &str,
+    installed_mods: &[InstalledMod],
+    state: &mut State,
+    opt: &Opt,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("reading subscriptions...");
+    let sync =
+        plan_subscription_sync(modio, direction, installed_mods, state, opt.platform).await?;
+    print_install_problems(&sync.installs);
+    if sync.plan.is_empty() {
+        println!("subscriptions and mods folder are in sync");
+    } else if !review(&sync.plan, path, opt)? {
+        return Ok(());
+    }
+    let pb = progress_bar();
+    for problem in apply_subscription_sync(modio, path, sync, state, opt.platform, &pb).await? {
+        println!("! {}", problem);
+    }
+    Ok(())
+}
+
+/// check for updates and print what was found besides the updates themselves
+async fn check_updates<'a>(
+    modio: &Modio,
+    installed_mods: &'a [InstalledMod],
+    state: &mut State,
+    full_scan: bool,
+) -> Result<UpdateCheck<'a>, Box<dyn std::error::Error>> {
+    let pb = progress_bar();
+    let check = updater::check_updates(modio, installed_mods, state, full_scan, &pb).await?;
+    match check.changed {
+        Some(changed) => println!(
+            "{} of {} mods changed since the last update",
+            changed, check.checked
+        ),
+        None => println!("checking all {} mods", check.checked),
+    }
+    for (barcode, reason) in &check.skipped {
+        println!("skipped {}: {}", barcode, reason);
+    }
+    if !check.held_back.is_empty() {
+        println!("pinned mods with updates available:");
+        for held in &check.held_back {
+            let available = match &held.version {
+                Some(x) => format!("{} (modfile {})", x, held.modfile_id),
+                None => format!("modfile {}", held.modfile_id),
+            };
+            println!(
+                "  {}: pinned {} (modfile {}), available {}",
+                held.installed.barcode(),
+                held.installed
+                    .manifest
+                    .pallet()
+                    .version
+                    .clone()
+                    .unwrap_or_default(),
+                held.pinned,
+                available
+            );
+        }
+    }
+    let unavailable: Vec<&Unavailable> = state.unavailable.values().filter(|x| !x.kept).collect();
+    if !unavailable.is_empty() {
+        println!("these mods are not available on mod.io anymore:");
+        for x in unavailable {
+            println!("  {} ({})", x.barcode, x.reason);
+        }
+        println!(
+            "use `unavailable keep`, `unavailable archive` or `unavailable remove` to deal with them"
+        );
+    }
+    Ok(check)
+}
+
+fn print_update(update: &PendingUpdate) {
+    println!(
+        "~ {} {} -> {} ({})",
+        update.installed.barcode(),
+        update
+            .installed
+            .manifest
+            .pallet()
+            .version
+            .clone()
+            .unwrap_or_default(),
+        update.modfile.version.clone().unwrap_or_default(),
+        format_size(update.modfile.filesize)
+    );
+}
+
+fn print_changelog(modfile: &modio::files::File) {
+    println!(
+        "    {} ({}):",
+        modfile.version.clone().unwrap_or_default(),
+        format_date(modfile.date_added.as_secs())
+    );
+    match modfile.changelog.as_deref().map(str::trim) {
+        Some(changelog) if !changelog.is_empty() => {
+            for line in changelog.lines() {
+                println!("      {}", line);
             }
-            if found {
-            } else {
-                download_mod(
-                    mod_,
-                    &modio,
-                    path.clone(),
-                    PathBuf::from(path.clone()),
-                    None,
-                    None,
-                    None,
-                )
-                .await?;
+        }
+        _ => println!("      no changelog"),
+    }
+}
+
+async fn outdated(
+    modio: &Modio,
+    installed_mods: &[InstalledMod],
+    state: &mut State,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let check = check_updates(modio, installed_mods, state, true).await?;
+    if check.updates.is_empty() {
+        println!("all mods are up to date");
+        return Ok(());
+    }
+    for update in &check.updates {
+        print_update(update);
+        for file in changelogs(modio, update).await? {
+            print_changelog(&file);
+        }
+    }
+    let total: u64 = check.updates.iter().map(|x| x.modfile.filesize).sum();
+    println!(
+        "{} mods can be updated, {} to download",
+        check.updates.len(),
+        format_size(total)
+    );
+    Ok(())
+}
+
+async fn update_all(
+    modio: &Modio,
+    path: &str,
+    installed_mods: &[InstalledMod],
+    state: &mut State,
+    opt: &Opt,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("updating all installed mods...");
+    let check = check_updates(modio, installed_mods, state, opt.full_scan).await?;
+    for update in &check.updates {
+        print_update(update);
+        print_changelog(&update.modfile);
+    }
+    if !check.updates.is_empty() && !review(&update_plan(&check.updates), path, opt)? {
+        return Ok(());
+    }
+
+    let pb = progress_bar();
+    pb.set_length(check.updates.len() as u64);
+    for update in &check.updates {
+        pb.inc(1);
+        pb.set_message(format!("Updating {}", update.installed.barcode()));
+        install_update(modio, path, update).await?;
+    }
+    pb.finish_and_clear();
+    state.last_sync = check.next_sync(state.last_sync);
+    Ok(())
+}
+
+/// rewrite manifests with current mod.io metadata, the pallet folders are not touched
+async fn refresh_metadata(
+    modio: &Modio,
+    path: &str,
+    installed_mods: &[InstalledMod],
+    opt: &Opt,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("fetching metadata of {} mods...", installed_mods.len());
+    let refresh = updater::refresh_metadata(modio, installed_mods).await?;
+    for (modfile_id, x) in &refresh.skipped {
+        println!("! could not get modfile {}: {}", modfile_id, x);
+    }
+    if !review(&refresh.plan, path, opt)? {
+        return Ok(());
+    }
+    for manifest in &refresh.manifests {
+        save_manifest(PathBuf::from(path), manifest)?;
+    }
+    Ok(())
+}
+
+fn unavailable(
+    cmd: &UnavailableCmd,
+    path: &str,
+    installed_mods: &[InstalledMod],
+    state: &mut State,
+    opt: &Opt,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let key = match cmd {
+        UnavailableCmd::List => {
+            for (mod_id, x) in &state.unavailable {
+                println!(
+                    "{} ({}) {} since {}{}",
+                    x.barcode,
+                    mod_id,
+                    x.reason,
+                    format_date(x.since),
+                    if x.kept { ", kept" } else { "" }
+                );
+            }
+            return Ok(());
+        }
+        UnavailableCmd::Keep { mod_ } => mod_,
+        UnavailableCmd::Archive { mod_ } => mod_,
+        UnavailableCmd::Remove { mod_ } => mod_,
+    };
+    let mod_id = mod_id_of(key, installed_mods)?;
+    if !state.unavailable.contains_key(&mod_id) {
+        throw(&format!("{} is not marked as unavailable", key))?;
+    }
+    let installed = installed_mods.iter().find(|x| x.mod_id() == Some(mod_id));
+    if let UnavailableCmd::Keep { .. } = cmd {
+        state.unavailable.get_mut(&mod_id).unwrap().kept = true;
+        return Ok(());
+    }
+    let mut plan = Plan::default();
+    if let Some(installed) = installed {
+        if let UnavailableCmd::Archive { .. } = cmd {
+            plan.actions
+                .push(Action::Archive(installed.barcode().to_string()));
+        }
+        plan.remove(installed.barcode());
+    }
+    if !plan.is_empty() && !review(&plan, path, opt)? {
+        return Ok(());
+    }
+    match cmd {
+        UnavailableCmd::Archive { .. } => {
+            if let Some(installed) = installed {
+                let archive = archive_mod(path, installed)?;
+                println!("archived {} to {}", installed.barcode(), archive);
+                remove_mod(path, installed)?;
+            }
+        }
+        _ => {
+            if let Some(installed) = installed {
+                remove_mod(path, installed)?;
             }
         }
-        pb.finish();
     }
+    state.unavailable.remove(&mod_id);
+    state.pins.remove(&mod_id);
+    state.dependencies.remove(&mod_id);
     Ok(())
 }
 
-async fn download_mod(
-    mod_: &Mod,
+async fn edit_mod(
+    cmd: &EditCmd,
     modio: &Modio,
-    path: String,
-    mod_folder: PathBuf,
-    manifest: Option<Manifest>,
-    modfile: Option<&modio::files::File>,
-    installed_date: Option<u128>,
+    opt: &Opt,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let modfile = match modfile {
-        Some(x) => x,
-        None => {
-            let modfile = &mod_.modfile;
-            let modfile = match modfile {
-                Some(x) => x,
-                None => {
-                    println!("no modfile");
-                    return Ok(());
-                }
+    let edit = match cmd {
+        EditCmd::Details {
+            mod_id,
+            name,
+            summary,
+            description_file,
+            visibility,
+        } => {
+            let description = match description_file {
+                Some(file) => Some(fs::read_to_string(file)?),
+                None => None,
             };
-            modfile
+            let details = Details {
+                name,
+                summary,
+                description,
+                public: visibility.as_ref().map(|x| x == "public"),
+            };
+            plan_details_edit(modio, *mod_id, details).await?
         }
+        EditCmd::Tags {
+            mod_id,
+            add,
+            remove,
+        } => plan_tags_edit(modio, *mod_id, add, remove).await?,
+        EditCmd::Logo { mod_id, file } => plan_logo_edit(modio, *mod_id, file).await?,
     };
-    let xdg_cache_home = env::var("XDG_CACHE_HOME").unwrap_or_else(|_| {
-        // Default to ~/.cache if XDG_CACHE_HOME is not set
-        let home = env::var("HOME").unwrap();
-        format!("{}/.cache", home)
-    });
-
-    let action = DownloadAction::File {
-        game_id: Id::new(BONELAB),
-        mod_id: Id::new(mod_.id.into()),
-        file_id: Id::new(modfile.id.into()),
-    };
-    let _output = Command::new("mkdir")
-        .arg(xdg_cache_home.clone() + "/bonelab-mod-manager")
-        .output() // Execute the command
-        .expect("Failed to execute command: mkdir");
-    modio
-        .download(action)
-        .await?
-        .save_to_file(format!(
-            "{}/bonelab-mod-manager/{}.zip",
-            &xdg_cache_home,
-            mod_.name.clone()
-        ))
-        .await?;
-
-    // shell commands to unzip and then figure out the barcode and pallet name and catalog name
-    let _output = Command::new("unzip")
-        .args([
-            "".to_string() + &xdg_cache_home + "/bonelab-mod-manager/" + &mod_.name.clone() + "",
-            "-d".into(),
-            path.clone() + "/" + &mod_.name,
-        ])
-        .output() // Execute the command
-        .expect("Failed to execute command: unzip");
-    let barcode = Command::new("ls")
-        .arg(path.clone() + "/" + &mod_.name)
-        .output()?
-        .stdout;
-    let barcode = String::from_utf8(barcode)?;
-    let zip_path = path.clone() + "/" + &mod_.name + "/" + &barcode;
-    let zip_contents = Command::new("ls")
-        .arg("".to_owned() + &zip_path.trim() + "")
-        .output()?;
-    let zip_contents = zip_contents.stdout;
-    let zip_contents = String::from_utf8(zip_contents)?;
-    let mut jsons = Vec::new();
-    for line in zip_contents.lines() {
-        if line.ends_with(".json") {
-            jsons.push(line);
-        }
-    }
-    assert_eq!(jsons.len(), 2);
-    if !jsons[0].ends_with("pallet.json") {
-        jsons.reverse();
-    }
-
-    let mani = match manifest {
-        Some(x) => x,
-        None => make_manifest(
-            mod_,
-            modfile,
-            &barcode,
-            jsons[0].trim(),
-            jsons[1].trim(),
-            installed_date,
-        ),
-    };
-    let mani_str = serde_json::to_string_pretty(&mani)?;
-    let mut save_path = mod_folder;
-    save_path.push(mani.clone().objects.pallet.palletBarcode + ".manifest");
-    let mut file = File::create(save_path)?;
-    file.write_all(mani_str.as_bytes())?;
-
-    let _output = Command::new("mv")
-        .arg(path.clone() + "/" + &mod_.name + "/" + &barcode.trim())
-        .arg(path.clone() + "/")
-        .output()?;
-    let _output = Command::new("rmdir")
-        .arg(path.clone() + "/" + &mod_.name)
-        .output()?;
+    if !review(&edit.plan, ".", opt)? {
+        return Ok(());
+    }
+    let name = edit.online_mod.name.clone();
+    apply_edit(modio, edit).await?;
+    println!("updated {}", name);
     Ok(())
 }
 
-fn make_manifest(
-    mod_: &Mod,
-    modfile: &modio::files::File,
-    barcode: &str,
-    pallet_name: &str,
-    catalog_name: &str,
-    installed_date: Option<u128>,
-) -> Manifest {
-    let barcode = barcode.trim();
-    let pallet_name = pallet_name.trim();
-    let catalog_name = catalog_name.trim();
-    let time_now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_millis();
-    let installed_date = match installed_date {
+async fn publish(
+    source: &Path,
+    mod_id: u64,
+    upload: &Upload<'_>,
+    modio: &Modio,
+    xdg_config_home: &str,
+    opt: &Opt,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let prepared = prepare_upload(modio, source, mod_id, upload).await?;
+    if let Some(build) = &prepared.build {
+        for missing in &build.missing {
+            println!("! {} has no {}", build.barcode, missing);
+        }
+        for x in &build.left_out {
+            println!("leaving out {}", x);
+        }
+    }
+    if !review(&prepared.plan, &env::temp_dir().to_string_lossy(), opt)? {
+        return Ok(());
+    }
+    println!("uploading {}...", format_size(prepared.size));
+    let modfile = publish::upload(&prepared, upload, xdg_config_home).await?;
+    println!(
+        "uploaded modfile {} of {}, version {}",
+        modfile.id,
+        prepared.online_mod.name,
+        modfile.version.unwrap_or_default()
+    );
+    Ok(())
+}
+
+/// cache every installed mod's logo and point thumbnailOverride at it, or back at mod.io
+async fn thumbnails(
+    set_override: bool,
+    clear_override: bool,
+    path: &str,
+    installed_mods: &[InstalledMod],
+    opt: &Opt,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let thumbnails = plan_thumbnails(installed_mods, set_override, clear_override, opt.platform)?;
+    if !review(&thumbnails.plan, path, opt)? {
+        return Ok(());
+    }
+    let pb = progress_bar();
+    pb.set_length(thumbnails.missing.len() as u64);
+    for (mod_id, url) in thumbnails.missing {
+        pb.inc(1);
+        if let Err(x) = cache_thumbnail(mod_id, &url).await {
+            pb.println(format!(
+                "could not download the logo of mod {}: {}",
+                mod_id, x
+            ));
+        }
+    }
+    pb.finish_and_clear();
+    set_thumbnail_overrides(path, installed_mods, set_override, clear_override)
+}
+
+/// install a pallet zip or folder without mod.io
+fn install_local(
+    source: &Path,
+    is_dir: bool,
+    path: &str,
+    opt: &Opt,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let local = stage_local(source, is_dir, path)?;
+    if !review(&local.plan, path, opt)? {
+        fs::remove_dir_all(&local.staged.folder)?;
+        return Ok(());
+    }
+    installer::install_local(&local, path, opt.platform)?;
+    println!(
+        "installed {} from {}",
+        local.staged.barcode,
+        source.display()
+    );
+    Ok(())
+}
+
+/// print the dependencies `plan_installs` could not resolve
+fn print_install_problems(planned: &InstallPlan) {
+    for (id, required_by, x) in &planned.unavailable {
+        println!(
+            "! dependency {} of {} is not available: {}",
+            id, required_by, x
+        );
+    }
+    for cycle in &planned.cycles {
+        println!("! dependency cycle: {}", cycle.join(" -> "));
+    }
+}
+
+/// install planned mods in order, returns the ids of the mods that were installed
+async fn install_planned(
+    modio: &Modio,
+    path: &str,
+    plan: Vec<PlannedInstall>,
+    state: &mut State,
+    platform: Platform,
+) -> Vec<u64> {
+    let pb = progress_bar();
+    let outcome = installer::install_planned(modio, path, plan, state, platform, &pb).await;
+    for (name, x) in outcome.failed {
+        println!("could not install {}: {}", name, x);
+    }
+    outcome.installed
+}
+
+async fn install(
+    mods: &[String],
+    modio: &Modio,
+    path: &str,
+    installed_mods: &[InstalledMod],
+    state: &mut State,
+    opt: &Opt,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut online_mods = Vec::new();
+    for key in mods {
+        let mod_id: u64 = match key.parse() {
+            Ok(x) => x,
+            Err(_) => throw(&format!("{} is not a mod id", key))?,
+        };
+        if installed_mods.iter().any(|x| x.mod_id() == Some(mod_id)) {
+            println!("{} is already installed", mod_id);
+            continue;
+        }
+        online_mods.push(modio.mod_(Id::new(BONELAB), Id::new(mod_id)).get().await?);
+    }
+    let planned = plan_installs(modio, online_mods, installed_mods, opt.platform).await?;
+    print_install_problems(&planned);
+    let installs = planned.installs;
+    if !review(&install_plan(&installs), path, opt)? {
+        return Ok(());
+    }
+    install_planned(modio, path, installs, state, opt.platform).await;
+    Ok(())
+}
+
+fn remove(
+    mods: &[String],
+    path: &str,
+    installed_mods: &[InstalledMod],
+    state: &mut State,
+    opt: &Opt,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut removing = Vec::new();
+    for key in mods {
+        match installed_mods.iter().find(|x| x.matches(key)) {
+            Some(x) => removing.push(x),
+            None => throw(&format!("{} is not installed", key))?,
+        }
+    }
+    let mut plan = Plan::default();
+    for mod_ in &removing {
+        plan.remove(mod_.barcode());
+        let dependents = mod_.mod_id().map_or(Vec::new(), |x| state.dependents(x));
+        let needed_by: Vec<&str> = installed_mods
+            .iter()
+            .filter(|x| !removing.iter().any(|y| y.path == x.path))
+            .filter(|x| x.mod_id().is_some_and(|id| dependents.contains(&id)))
+            .map(|x| x.barcode())
+            .collect();
+        if !needed_by.is_empty() {
+            println!("! {} is needed by {}", mod_.barcode(), needed_by.join(", "));
+        }
+    }
+    if !review(&plan, path, opt)? {
+        return Ok(());
+    }
+    for mod_ in removing {
+        remove_mod(path, mod_)?;
+        if let Some(id) = mod_.mod_id() {
+            state.pins.remove(&id);
+            state.dependencies.remove(&id);
+        }
+    }
+    Ok(())
+}
+
+/// turn a mod id or the barcode of an installed mod into a mod id
+fn mod_id_of(
+    key: &str,
+    installed_mods: &[InstalledMod],
+) -> Result<u64, Box<dyn std::error::Error>> {
+    match installed_mods.iter().find(|x| x.matches(key)) {
+        Some(x) => Ok(x.mod_id().unwrap()),
+        None => match key.parse() {
+            Ok(x) => Ok(x),
+            Err(_) => throw(&format!("{} is not installed", key)),
+        },
+    }
+}
+
+async fn versions(
+    key: &str,
+    modio: &Modio,
+    installed_mods: &[InstalledMod],
+    platform: Platform,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mod_id = mod_id_of(key, installed_mods)?;
+    let installed = installed_mods
+        .iter()
+        .find(|x| x.mod_id() == Some(mod_id))
+        .map(|x| x.target().modfileId);
+    let modref = modio.mod_(Id::new(BONELAB), Id::new(mod_id));
+    let online_mod = modref.clone().get().await?;
+    let live = live_modfile_id(&online_mod, platform);
+    let files = modref.files().search(fid::desc()).collect().await?;
+    println!("{} ({} modfiles)", online_mod.name, files.len());
+    for file in files {
+        let platforms: Vec<&str> = file.platforms.iter().map(|x| x.target.as_str()).collect();
+        let mut marks = Vec::new();
+        if Some(file.id.get()) == installed {
+            marks.push("installed");
+        }
+        if Some(file.id.get()) == live {
+            marks.push("live");
+        }
+        println!(
+            "  {} {:<12} {} {:>10} [{}] {}",
+            file.id,
+            file.version.unwrap_or_default(),
+            format_date(file.date_added.as_secs()),
+            format_size(file.filesize),
+            platforms.join(", "),
+            marks.join(", ")
+        );
+    }
+    Ok(())
+}
+
+async fn rollback(
+    key: &str,
+    to: &Option<String>,
+    modio: &Modio,
+    path: &str,
+    installed_mods: &[InstalledMod],
+    state: &State,
+    opt: &Opt,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let installed = match installed_mods.iter().find(|x| x.matches(key)) {
         Some(x) => x,
-        None => time_now,
+        None => throw(&format!("{} is not installed", key))?,
     };
-    let mut targets = HashMap::new();
-    targets.insert(
-        "pc".to_string(),
-        Reference {
-            reference: "3".into(),
-            type_: "mod-target-modio#0".into(),
-        },
+    let target = installed.target();
+    let rollback = match rollback_target(modio, installed, to).await? {
+        Some(x) => x,
+        None => {
+            println!(
+                "{} is already at modfile {}",
+                installed.barcode(),
+                target.modfileId
+            );
+            return Ok(());
+        }
+    };
+    println!(
+        "rolling {} back from {} to {} (modfile {})",
+        installed.barcode(),
+        installed
+            .manifest
+            .pallet()
+            .version
+            .clone()
+            .unwrap_or_default(),
+        rollback.modfile.version.clone().unwrap_or_default(),
+        rollback.modfile.id
+    );
+
+    let mut plan = Plan::default();
+    plan.install(
+        &rollback.online_mod.name,
+        rollback.modfile.filesize,
+        rollback.modfile.filesize_uncompressed,
+        Some(installed.barcode()),
     );
-    let manifest = Manifest {
-        version: 2,
-        root: Root {
-            reference: "1".into(),
-            type_: "pallet-manifest#0".into(),
+    if !review(&plan, path, opt)? {
+        return Ok(());
+    }
+    download_mod(
+        &rollback.online_mod,
+        modio,
+        Path::new(path),
+        opt.platform,
+        DownloadOptions {
+            modfile: Some(&rollback.modfile),
+            manifest: Some(rollback.manifest),
+            installed_date: None,
         },
-        objects: Object {
-            pallet: Pallet {
-                palletBarcode: barcode.into(),
-                palletPath: format!(
-                    "C:/users/steamuser/AppData/LocalLow/Stress Level Zero/BONELAB/Mods/{}/{}",
-                    barcode, pallet_name
+    )
+    .await?;
+    if !state.pins.contains_key(&target.modId) {
+        println!("run `pin {}` to stop updates from undoing this", key);
+    }
+    Ok(())
+}
+
+fn pin(
+    mods: &[String],
+    installed_mods: &[InstalledMod],
+    state: &mut State,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if mods.is_empty() {
+        for (mod_id, modfile_id) in &state.pins {
+            match installed_mods.iter().find(|x| x.mod_id() == Some(*mod_id)) {
+                Some(x) => println!(
+                    "{} ({}) pinned at modfile {}",
+                    x.barcode(),
+                    mod_id,
+                    modfile_id
                 ),
-                catalogPath: format!(
-                    "C:/users/steamuser/AppData/LocalLow/Stress Level Zero/BONELAB/Mods/{}/{}",
-                    barcode, catalog_name
+                None => println!(
+                    "{} (not installed) pinned at modfile {}",
+                    mod_id, modfile_id
                 ),
-                version: modfile.version.clone(),
-                installedDate: installed_date.to_string(),
-                updateDate: time_now.to_string(),
-                modListing: Some(Reference {
-                    reference: "2".into(),
-                    type_: "mod-listing#0".into(),
-                }),
-                active: true,
-                isa: Isa {
-                    type_: "pallet-manifest#0".into(),
-                },
-            },
-            mod_listing: Some(ModListing {
-                barcode: barcode.into(),
-                title: Some(mod_.name.clone()),
-                description: mod_.description_plaintext.clone(),
-                author: Some(mod_.submitted_by.username.clone()),
-                version: modfile.version.clone(),
-                thumbnailUrl: Some(mod_.logo.thumb_320x180.to_string()),
-                targets: targets,
-                isa: Isa {
-                    type_: "mod-listing#0".into(),
-                },
-            }),
-            mod_target: Some(ModTarget {
-                thumbnailOverride: None,
-                gameId: mod_.game_id.into(),
-                modId: mod_.id.into(),
-                modfileId: modfile.id.into(),
-                isa: Isa {
-                    type_: "mod-target-modio#0".into(),
-                },
-            }),
-        },
-    };
-    return manifest;
+            }
+        }
+        return Ok(());
+    }
+    for key in mods {
+        let target = match installed_mods.iter().find(|x| x.matches(key)) {
+            Some(x) => x.target(),
+            None => throw(&format!("{} is not installed", key))?,
+        };
+        state.pins.insert(target.modId, target.modfileId);
+        println!("pinned {} at modfile {}", key, target.modfileId);
+    }
+    Ok(())
+}
+
+fn export(
+    file: &Path,
+    installed_mods: &[InstalledMod],
+    dry_run: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let modlist = modlist(installed_mods);
+    if dry_run {
+        println!("would export {} mods to {}", modlist.len(), file.display());
+        return Ok(());
+    }
+    write_modlist(file, &modlist)?;
+    println!("exported {} mods to {}", modlist.len(), file.display());
+    Ok(())
+}
+
+/// install the modfiles of a modlist one by one
+async fn install_modfiles(
+    modio: &Modio,
+    path: &str,
+    installs: &[ModInstall<'_>],
+    platform: Platform,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pb = progress_bar();
+    pb.set_length(installs.len() as u64);
+    for install in installs {
+        pb.inc(1);
+        pb.set_message(format!("installing {}", install.online_mod.name));
+        install_modfile(modio, path, install, platform).await?;
+    }
+    pb.finish_and_clear();
+    Ok(())
+}
+
+async fn import(
+    file: &Path,
+    modio: &Modio,
+    path: &str,
+    installed_mods: &[InstalledMod],
+    opt: &Opt,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let modlist = read_modlist(file)?;
+    println!("looking up {} mods...", modlist.len());
+    let import = plan_import(modio, &modlist, installed_mods, opt.platform).await?;
+    if !import.unavailable.is_empty() {
+        println!("these mods cannot be imported:");
+        for (entry, err) in &import.unavailable {
+            println!(
+                "  {} (mod {}, file {}): {}",
+                entry.barcode, entry.mod_id, entry.modfile_id, err
+            );
+        }
+    }
+    if !review(&import.plan, path, opt)? {
+        return Ok(());
+    }
+    install_modfiles(modio, path, &import.installs, opt.platform).await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn sync(
+    file: &Path,
+    lockfile: &Path,
+    locked: bool,
+    update: bool,
+    modio: &Modio,
+    path: &str,
+    installed_mods: &[InstalledMod],
+    state: &mut State,
+    opt: &Opt,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let desired = read_desired(file)?;
+    let old_lock = read_lockfile(lockfile)?;
+    println!("resolving {} mods...", desired.mods.len());
+    let lock = resolve_lock(modio, &desired, &old_lock, locked, update, opt.platform).await?;
+    let sync = plan_sync(modio, &lock, installed_mods, state, opt.platform).await?;
+    print_install_problems(&sync.dependencies);
+    if sync.plan.is_empty() {
+        if !locked && !opt.dry_run {
+            write_lockfile(lockfile, &lock, &old_lock, opt.platform)?;
+        }
+        println!("mods folder is in sync");
+        return Ok(());
+    }
+    if !review(&sync.plan, path, opt)? {
+        return Ok(());
+    }
+    // the lockfile only changes once the plan is approved
+    if !locked {
+        write_lockfile(lockfile, &lock, &old_lock, opt.platform)?;
+    }
+    let pb = progress_bar();
+    for (name, x) in apply_sync(modio, path, sync, state, opt.platform, &pb).await? {
+        println!("could not install {}: {}", name, x);
+    }
+    Ok(())
+}
+
+async fn profile(
+    cmd: &ProfileCmd,
+    opt: &Opt,
+    xdg_config_home: &str,
+    path: &str,
+    installed_mods: &[InstalledMod],
+    local_mods: &[InstalledMod],
+    state: &mut State,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match cmd {
+        ProfileCmd::List => {
+            for (name, mods) in &state.profiles {
+                println!("{} ({} mods)", name, mods.len());
+            }
+        }
+        ProfileCmd::Show { name } => {
+            let mods = match state.profiles.get(name) {
+                Some(x) => x,
+                None => throw("No profile with that name")?,
+            };
+            for key in mods {
+                match installed_mods
+                    .iter()
+                    .chain(local_mods)
+                    .find(|x| x.matches(key))
+                {
+                    Some(x) => println!("{} ({})", key, x.barcode()),
+                    None => println!("{} (not installed)", key),
+                }
+            }
+        }
+        ProfileCmd::Save { name, mods } => {
+            let mods = if mods.is_empty() {
+                installed_mods
+                    .iter()
+                    .chain(local_mods)
+                    .filter(|x| x.manifest.pallet().active)
+                    .map(|x| match x.mod_id() {
+                        Some(id) => id.to_string(),
+                        None => x.barcode().to_string(),
+                    })
+                    .collect()
+            } else {
+                mods.clone()
+            };
+            println!("saved profile {} with {} mods", name, mods.len());
+            state.profiles.insert(name.clone(), mods);
+        }
+        ProfileCmd::Delete { name } => {
+            if state.profiles.remove(name).is_none() {
+                throw("No profile with that name")?;
+            }
+        }
+        ProfileCmd::Activate { name, uninstall } => {
+            let profile = match state.profiles.get(name) {
+                Some(x) => x,
+                None => throw("No profile with that name")?,
+            };
+            let activation =
+                plan_activation(profile, installed_mods, local_mods, state, *uninstall);
+            for key in &activation.unknown {
+                println!("! {} is not installed and is not a mod id", key);
+            }
+            if activation.is_empty() {
+                println!("mods folder already matches profile {}", name);
+                return Ok(());
+            }
+            let mut installs = Vec::new();
+            let mut modio = None;
+            if !activation.install.is_empty() {
+                let client = login(opt, xdg_config_home).await?;
+                let (online_mods, missing) = fetch_profile_mods(&client, &activation.install).await;
+                for (id, x) in missing {
+                    println!("! could not find mod {}: {}", id, x);
+                }
+                let planned =
+                    plan_installs(&client, online_mods, installed_mods, opt.platform).await?;
+                print_install_problems(&planned);
+                installs = planned.installs;
+                modio = Some(client);
+            }
+            if !review(&activation.plan(&installs), path, opt)? {
+                return Ok(());
+            }
+            let pb = progress_bar();
+            let failed = apply_activation(
+                modio.as_ref(),
+                path,
+                activation,
+                installs,
+                state,
+                opt.platform,
+                &pb,
+            )
+            .await?;
+            for (mod_name, x) in failed {
+                println!("could not install {}: {}", mod_name, x);
+            }
+            println!("activated profile {}", name);
+        }
+    }
+    Ok(())
+}
+
+async fn adopt(
+    only: &Option<String>,
+    mod_id: Option<u64>,
+    modio: &Modio,
+    path: &str,
+    opt: &Opt,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let untracked = adoptable(path, opt.platform, only)?;
+    for (name, x) in &untracked.unreadable {
+        println!("! could not read {}: {}", name, x);
+    }
+    for folder in &untracked.mismatched {
+        println!("! {} does not match the barcode in its pallet json", folder);
+    }
+    let untracked = untracked.pallets;
+    if untracked.is_empty() {
+        println!("every pallet is already tracked");
+        return Ok(());
+    }
+
+    println!("matching {} pallets on mod.io...", untracked.len());
+    let adoption = plan_adoption(modio, &untracked, mod_id, path, opt.platform).await?;
+    for (barcode, version) in &adoption.assumed_live {
+        println!(
+            "{}: no modfile has version {}, assuming the live one",
+            barcode,
+            version.clone().unwrap_or_default()
+        );
+    }
+    for (barcode, skipped) in &adoption.skipped {
+        match skipped {
+            Skipped::NoMatch => println!("{}: no matching mod on mod.io", barcode),
+            Skipped::Ambiguous(found) => {
+                println!(
+                    "{} matches more than one mod, adopt it with --mod-id:",
+                    barcode
+                );
+                for x in found {
+                    println!("  {} {} by {}", x.id, x.name, x.submitted_by.username);
+                }
+            }
+            Skipped::NoModfile(name) => {
+                println!("{}: {} has no {} modfile", barcode, name, opt.platform)
+            }
+            Skipped::NoPalletFiles => println!("! {} has no pallet and catalog json", barcode),
+        }
+    }
+    if !review(&adoption.plan, path, opt)? {
+        return Ok(());
+    }
+    for manifest in &adoption.manifests {
+        save_manifest(PathBuf::from(path), manifest)?;
+    }
+    Ok(())
+}
+
+/// report pallets whose dependencies are not installed, returns the missing barcodes
+fn doctor(path: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let report = missing_dependencies(path)?;
+    for (folder, x) in &report.unreadable {
+        println!("! could not read the pallet json of {}: {}", folder, x);
+    }
+    for (folder, dependency) in &report.missing {
+        println!("{} needs {}, which is not installed", folder, dependency);
+    }
+    if report.missing.is_empty() {
+        println!("all {} pallets have their dependencies", report.pallets);
+    }
+    Ok(report
+        .missing_barcodes()
+        .into_iter()
+        .map(String::from)
+        .collect())
+}
+
+/// search mod.io for mods that could provide missing pallets
+async fn find_missing_pallets(
+    missing: &[String],
+    modio: &Modio,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for barcode in missing {
+        let found = search_pallet(modio, barcode).await?;
+        if found.is_empty() {
+            println!("{}: no matching mods on mod.io", barcode);
+            continue;
+        }
+        println!("{} might be provided by:", barcode);
+        for mod_ in found {
+            println!(
+                "  {} {} by {} ({})",
+                mod_.id, mod_.name, mod_.submitted_by.username, mod_.profile_url
+            );
+        }
+    }
+    Ok(())
+}
+
+fn progress_bar() -> ProgressBar {
+    let pb = ProgressBar::new(0);
+    pb.set_style(ProgressStyle::with_template(TEMPLATE).unwrap());
+    pb
+}
+
+async fn login(opt: &Opt, xdg_config_home: &str) -> Result<Modio, Box<dyn std::error::Error>> {
+    let (modio, username) = bonelab_mod_manager::client::login(
+        opt.api_key.clone(),
+        opt.email.as_deref(),
+        xdg_config_home,
+        || prompt("security code: "),
+    )
+    .await?;
+    println!("logged in as {}", username);
+    Ok(modio)
+}
+
+/// print a plan, then stop on --dry-run or when it does not fit on disk, otherwise ask
+fn review(plan: &Plan, path: &str, opt: &Opt) -> Result<bool, Box<dyn std::error::Error>> {
+    if plan.is_empty() {
+        println!("nothing to do");
+        return Ok(false);
+    }
+    println!("plan:");
+    print!("{}", plan);
+    if opt.dry_run {
+        println!("dry run, nothing was changed");
+        return Ok(false);
+    }
+    let cache = xdg_dir("XDG_CACHE_HOME", ".cache")? + "/bonelab-mod-manager";
+    if let Some((disk, needed)) = plan.short_disks(path, &cache).first() {
+        throw(&format!(
+            "not enough disk space on {}, {} needed but only {} free",
+            disk.mount,
+            format_size(*needed),
+            format_size(disk.free)
+        ))?;
+    }
+    Ok(confirm("apply this plan?", opt.yes)?)
+}
+
+fn confirm(question: &str, yes: bool) -> io::Result<bool> {
+    if yes {
+        println!("{} yes", question);
+        return Ok(true);
+    }
+    let answer = prompt(&format!("{} [y/N] ", question))?;
+    Ok(answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes"))
 }
 
 fn prompt(prompt: &str) -> io::Result<String> {
@@ -0,0 +1,497 @@
+//! reading and writing `.manifest` files and the pallet jsons they point at
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use indicatif::ProgressBar;
+use modio::mods::Mod;
+use serde_json::Map;
+
+use crate::client::live_modfile_id;
+use crate::installer::Staged;
+use crate::structs::{
+    Isa, MOD_LISTING, MOD_TARGET_MODIO, Manifest, ModListing, ModTarget, PALLET_MANIFEST, Pallet,
+    PalletJson, Platform,
+};
+
+#[derive(Clone)]
+/// a mod in the mods folder that came from mod.io
+pub struct InstalledMod {
+    /// path of the `.manifest` file
+    pub path: String,
+    /// the parsed `.manifest` file
+    pub manifest: Manifest,
+    /// platform of the mods folder, the manifest has a target for it unless it is in `InstalledMods::local`
+    pub platform: Platform,
+}
+
+impl InstalledMod {
+    /// barcode of the pallet, also the name of its folder
+    pub fn barcode(&self) -> &str {
+        &self.manifest.pallet().palletBarcode
+    }
+
+    /// the mod.io target for the platform of the mods folder
+    pub fn target(&self) -> &ModTarget {
+        self.manifest.mod_target(self.platform).unwrap()
+    }
+
+    /// the mod.io mod id, if the manifest has a mod target
+    pub fn mod_id(&self) -> Option<u64> {
+        self.manifest.mod_target(self.platform).map(|x| x.modId)
+    }
+
+    /// check if a mod id or barcode refers to this mod
+    pub fn matches(&self, key: &str) -> bool {
+        self.barcode() == key || self.mod_id().is_some_and(|x| x.to_string() == key)
+    }
+}
+
+/// the mods folder as `read_installed_mods` found it
+pub struct InstalledMods {
+    /// mods with a mod.io target for the platform
+    pub mods: Vec<InstalledMod>,
+    /// manifests that could not be read, and why
+    pub unreadable: Vec<(String, String)>,
+    /// barcodes of mods installed for another platform only, and that platform
+    pub other_platform: Vec<(String, Platform)>,
+    /// manifests without any mod.io target, from local installs or written by hand,
+    /// `InstalledMod::target` must not be called on them
+    pub local: Vec<InstalledMod>,
+}
+
+/// read the manifests of the mods managed through mod.io for a platform
+pub fn read_installed_mods(
+    path: &str,
+    platform: Platform,
+    pb: &ProgressBar,
+) -> Result<InstalledMods, Box<dyn std::error::Error>> {
+    let mut mod_manifests = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if name.ends_with(".manifest") && !name.starts_with("SLZ") {
+            mod_manifests.push(name);
+        }
+    }
+
+    let mut installed = InstalledMods {
+        mods: Vec::new(),
+        unreadable: Vec::new(),
+        other_platform: Vec::new(),
+        local: Vec::new(),
+    };
+    pb.set_length(mod_manifests.len() as u64);
+    for manifest in mod_manifests {
+        let path = path.to_string() + &manifest;
+        let manifest = fs::read_to_string(&path)?;
+        let manifest = match Manifest::parse(&manifest) {
+            Ok(x) => x,
+            Err(x) => {
+                installed.unreadable.push((path, x.to_string()));
+                continue;
+            }
+        };
+        if manifest.mod_target(platform).is_none() {
+            let other = Platform::ALL
+                .into_iter()
+                .find(|x| manifest.mod_target(*x).is_some());
+            match other {
+                Some(other) => installed
+                    .other_platform
+                    .push((manifest.pallet().palletBarcode.clone(), other)),
+                None => installed.local.push(InstalledMod {
+                    path,
+                    manifest,
+                    platform,
+                }),
+            }
+            continue;
+        }
+        installed.mods.push(InstalledMod {
+            path,
+            manifest,
+            platform,
+        });
+        pb.inc(1);
+    }
+    pb.finish_and_clear();
+    Ok(installed)
+}
+
+/// write a manifest into the mods folder, named after its barcode
+pub fn save_manifest(
+    mut mod_folder: PathBuf,
+    manifest: &Manifest,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mani_str = serde_json::to_string_pretty(manifest)?;
+    mod_folder.push(manifest.pallet().palletBarcode.clone() + ".manifest");
+    let mut file = File::create(mod_folder)?;
+    file.write_all(mani_str.as_bytes())?;
+    Ok(())
+}
+
+/// manifest for a pallet installed from a mod.io modfile
+///
+/// the listing gets a target for every platform the mod has a live modfile for,
+/// the installed modfile is the target of `platform`
+#[allow(clippy::too_many_arguments)]
+pub fn make_manifest(
+    mod_: &Mod,
+    modfile: &modio::files::File,
+    barcode: &str,
+    pallet_name: &str,
+    catalog_name: &str,
+    installed_date: Option<u128>,
+    platform: Platform,
+) -> Manifest {
+    let barcode = barcode.trim();
+    let pallet_name = pallet_name.trim();
+    let catalog_name = catalog_name.trim();
+    let time_now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    let installed_date = match installed_date {
+        Some(x) => x,
+        None => time_now,
+    };
+    let pallet = Pallet {
+        palletBarcode: barcode.into(),
+        palletPath: format!("{}/{}/{}", platform.game_mod_folder(), barcode, pallet_name),
+        catalogPath: format!(
+            "{}/{}/{}",
+            platform.game_mod_folder(),
+            barcode,
+            catalog_name
+        ),
+        version: modfile.version.clone(),
+        installedDate: installed_date.to_string(),
+        updateDate: time_now.to_string(),
+        modListing: None,
+        active: true,
+        local: false,
+        isa: Isa {
+            type_: PALLET_MANIFEST.into(),
+        },
+        extra: Map::new(),
+    };
+    let listing = ModListing {
+        barcode: barcode.into(),
+        title: Some(mod_.name.clone()),
+        description: mod_.description_plaintext.clone(),
+        author: Some(mod_.submitted_by.username.clone()),
+        version: modfile.version.clone(),
+        thumbnailUrl: Some(mod_.logo.thumb_320x180.to_string()),
+        targets: BTreeMap::new(),
+        isa: Isa {
+            type_: MOD_LISTING.into(),
+        },
+        extra: Map::new(),
+    };
+    let targets = Platform::ALL
+        .into_iter()
+        .filter_map(|x| {
+            let modfile_id = match x == platform {
+                true => modfile.id.get(),
+                false => live_modfile_id(mod_, x)?,
+            };
+            let target = ModTarget {
+                thumbnailOverride: None,
+                gameId: mod_.game_id.into(),
+                modId: mod_.id.into(),
+                modfileId: modfile_id,
+                isa: Isa {
+                    type_: MOD_TARGET_MODIO.into(),
+                },
+                extra: Map::new(),
+            };
+            Some((x, target))
+        })
+        .collect();
+    Manifest::new(pallet, Some(listing), targets)
+}
+
+/// manifest for a pallet that is not on mod.io, it has no mod target
+pub fn make_local_manifest(
+    staged: &Staged,
+    pallet: Option<PalletJson>,
+    platform: Platform,
+) -> Manifest {
+    let time_now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    let object = pallet.as_ref().and_then(|x| x.pallet());
+    let version = object.and_then(|x| x.version.clone());
+    let pallet = Pallet {
+        palletBarcode: staged.barcode.clone(),
+        palletPath: format!(
+            "{}/{}/{}",
+            platform.game_mod_folder(),
+            staged.barcode,
+            staged.pallet_name
+        ),
+        catalogPath: format!(
+            "{}/{}/{}",
+            platform.game_mod_folder(),
+            staged.barcode,
+            staged.catalog_name
+        ),
+        version: version.clone(),
+        installedDate: time_now.to_string(),
+        updateDate: time_now.to_string(),
+        modListing: None,
+        active: true,
+        local: true,
+        isa: Isa {
+            type_: PALLET_MANIFEST.into(),
+        },
+        extra: Map::new(),
+    };
+    let listing = ModListing {
+        barcode: staged.barcode.clone(),
+        title: object.and_then(|x| x.title.clone()),
+        description: None,
+        author: object.and_then(|x| x.author.clone()),
+        version,
+        thumbnailUrl: None,
+        targets: BTreeMap::new(),
+        isa: Isa {
+            type_: MOD_LISTING.into(),
+        },
+        extra: Map::new(),
+    };
+    Manifest::new(pallet, Some(listing), Vec::new())
+}
+
+/// a manifest with the listing and version of a modfile, the rest is kept
+pub fn refreshed_manifest(
+    manifest: &Manifest,
+    online_mod: &Mod,
+    modfile: &modio::files::File,
+) -> Manifest {
+    let mut manifest = manifest.clone();
+    manifest.pallet_mut().version = modfile.version.clone();
+    if let Some(listing) = manifest.mod_listing_mut() {
+        listing.title = Some(online_mod.name.clone());
+        listing.description = online_mod.description_plaintext.clone();
+        listing.author = Some(online_mod.submitted_by.username.clone());
+        listing.version = modfile.version.clone();
+        listing.thumbnailUrl = Some(online_mod.logo.thumb_320x180.to_string());
+    }
+    manifest
+}
+
+/// names of the listing fields that differ between two manifests
+pub fn metadata_changes(old: &Manifest, new: &Manifest) -> Vec<&'static str> {
+    let mut changes = Vec::new();
+    if old.pallet().version != new.pallet().version {
+        changes.push("version");
+    }
+    if let (Some(old), Some(new)) = (old.mod_listing(), new.mod_listing()) {
+        if old.title != new.title {
+            changes.push("title");
+        }
+        if old.description != new.description {
+            changes.push("description");
+        }
+        if old.author != new.author {
+            changes.push("author");
+        }
+        if old.thumbnailUrl != new.thumbnailUrl {
+            changes.push("thumbnail");
+        }
+    }
+    changes
+}
+
+/// read the pallet json in a pallet folder, if there is one
+pub fn read_pallet_json(folder: &Path) -> Result<Option<PalletJson>, Box<dyn std::error::Error>> {
+    for entry in fs::read_dir(folder)? {
+        let entry = entry?;
+        if entry
+            .file_name()
+            .to_string_lossy()
+            .ends_with(".pallet.json")
+        {
+            let pallet = fs::read_to_string(entry.path())?;
+            return Ok(Some(serde_json::from_str(&pallet)?));
+        }
+    }
+    Ok(None)
+}
+
+/// names of the pallet json and catalog json in a pallet folder
+pub fn pallet_files(folder: &Path) -> Result<Option<(String, String)>, Box<dyn std::error::Error>> {
+    let mut pallet = None;
+    let mut catalog = None;
+    for entry in fs::read_dir(folder)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if name.ends_with("pallet.json") {
+            pallet = Some(name);
+        } else if name.ends_with(".json") {
+            catalog = Some(name);
+        }
+    }
+    Ok(pallet.zip(catalog))
+}
+
+/// a pallet in the mods folder without a mod.io target
+pub struct Untracked {
+    /// name of the pallet folder
+    pub barcode: String,
+    /// title from the pallet json
+    pub title: Option<String>,
+    /// author from the pallet json
+    pub author: Option<String>,
+    /// version from the pallet json
+    pub version: Option<String>,
+    /// the manifest it already has, if any
+    pub manifest: Option<Manifest>,
+}
+
+/// what `find_untracked` found
+pub struct UntrackedPallets {
+    /// the untracked pallets
+    pub pallets: Vec<Untracked>,
+    /// manifests and pallet jsons that could not be read, and why
+    pub unreadable: Vec<(String, String)>,
+    /// pallet folders named differently from the barcode in their pallet json
+    pub mismatched: Vec<String>,
+}
+
+/// manifests without a mod target for the platform and pallet folders without a manifest
+pub fn find_untracked(
+    path: &str,
+    platform: Platform,
+) -> Result<UntrackedPallets, Box<dyn std::error::Error>> {
+    let mut untracked = UntrackedPallets {
+        pallets: Vec::new(),
+        unreadable: Vec::new(),
+        mismatched: Vec::new(),
+    };
+    let mut manifests = HashSet::new();
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.ends_with(".manifest") {
+            continue;
+        }
+        let manifest = match Manifest::parse(&fs::read_to_string(entry.path())?) {
+            Ok(x) => x,
+            Err(x) => {
+                untracked.unreadable.push((name, x.to_string()));
+                continue;
+            }
+        };
+        manifests.insert(manifest.pallet().palletBarcode.clone());
+        if manifest.mod_target(platform).is_some() {
+            continue;
+        }
+        let listing = manifest.mod_listing();
+        untracked.pallets.push(Untracked {
+            barcode: manifest.pallet().palletBarcode.clone(),
+            title: listing.and_then(|x| x.title.clone()),
+            author: listing.and_then(|x| x.author.clone()),
+            version: manifest.pallet().version.clone(),
+            manifest: Some(manifest),
+        });
+    }
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let folder = entry.file_name().to_string_lossy().into_owned();
+        if !entry.file_type()?.is_dir() || manifests.contains(&folder) {
+            continue;
+        }
+        let pallet = match read_pallet_json(&entry.path()) {
+            Ok(Some(x)) => x,
+            Ok(None) => continue,
+            Err(x) => {
+                untracked.unreadable.push((folder, x.to_string()));
+                continue;
+            }
+        };
+        if pallet.barcode() != Some(folder.as_str()) {
+            untracked.mismatched.push(folder);
+            continue;
+        }
+        let object = pallet.pallet().unwrap();
+        untracked.pallets.push(Untracked {
+            barcode: folder,
+            title: object.title.clone(),
+            author: object.author.clone(),
+            version: object.version.clone(),
+            manifest: None,
+        });
+    }
+    Ok(untracked)
+}
+
+/// the pallets in a mods folder and the dependencies that are not installed, see `missing_dependencies`
+pub struct DependencyReport {
+    /// how many pallet jsons were read
+    pub pallets: usize,
+    /// pallet folders and the barcodes they need that are not installed
+    pub missing: Vec<(String, String)>,
+    /// pallet folders whose pallet json could not be read, and why
+    pub unreadable: Vec<(String, String)>,
+}
+
+impl DependencyReport {
+    /// every missing barcode once
+    pub fn missing_barcodes(&self) -> Vec<&str> {
+        let mut barcodes: Vec<&str> = Vec::new();
+        for (_, barcode) in &self.missing {
+            if !barcodes.contains(&barcode.as_str()) {
+                barcodes.push(barcode);
+            }
+        }
+        barcodes
+    }
+}
+
+/// read the pallet json of every pallet folder and find the dependencies that are not installed,
+/// `SLZ` pallets ship with the game and are never missing
+pub fn missing_dependencies(path: &str) -> Result<DependencyReport, Box<dyn std::error::Error>> {
+    let mut pallets = Vec::new();
+    let mut installed = HashSet::new();
+    let mut unreadable = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let folder = entry.file_name().to_string_lossy().into_owned();
+        installed.insert(folder.clone());
+        match read_pallet_json(&entry.path()) {
+            Ok(Some(pallet)) => {
+                if let Some(barcode) = pallet.barcode() {
+                    installed.insert(barcode.to_string());
+                }
+                pallets.push((folder, pallet));
+            }
+            Ok(None) => {}
+            Err(x) => unreadable.push((folder, x.to_string())),
+        }
+    }
+
+    let mut missing = Vec::new();
+    for (folder, pallet) in &pallets {
+        for dependency in pallet.dependencies() {
+            if installed.contains(&dependency) || dependency.starts_with("SLZ.") {
+                continue;
+            }
+            missing.push((folder.clone(), dependency));
+        }
+    }
+    Ok(DependencyReport {
+        pallets: pallets.len(),
+        missing,
+        unreadable,
+    })
+}
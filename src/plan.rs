@@ -0,0 +1,179 @@
+//! describing what a command is about to change before it changes anything
+
+use crate::format_size;
+use std::path::Path;
+use std::process::Command;
+
+/// one change a command will make to mod.io or the mods folder
+pub enum Action {
+    /// subscribe to a mod on mod.io
+    Subscribe(String),
+    /// unsubscribe from a mod on mod.io
+    Unsubscribe(String),
+    /// size is the zip, unpacked is what it takes once extracted
+    Download {
+        /// name and version of the mod
+        name: String,
+        /// bytes of the zip
+        size: u64,
+        /// bytes of the extracted files
+        unpacked: u64,
+    },
+    /// a new pallet folder
+    CreateFolder(String),
+    /// a pallet folder replaced by a new version
+    ReplaceFolder(String),
+    /// a pallet folder removed from the mods folder
+    DeleteFolder(String),
+    /// a pallet zipped before it is removed
+    Archive(String),
+    /// a `.manifest` written or rewritten
+    WriteManifest(String),
+    /// a `.manifest` removed
+    DeleteManifest(String),
+    /// a modfile uploaded to mod.io
+    Upload(String),
+    /// a mod logo downloaded into the thumbnail cache
+    CacheThumbnail(String),
+    /// a change to a mod page on mod.io
+    Edit(String),
+}
+
+/// everything a command is about to do, built before anything is changed
+#[derive(Default)]
+pub struct Plan {
+    /// the changes in the order they are made
+    pub actions: Vec<Action>,
+}
+
+impl Plan {
+    /// true when there is nothing to do
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    /// download a modfile into a new pallet folder, or over an installed one
+    pub fn install(&mut self, name: &str, size: u64, unpacked: u64, installed: Option<&str>) {
+        self.actions.push(Action::Download {
+            name: name.to_string(),
+            size,
+            unpacked,
+        });
+        match installed {
+            Some(barcode) => {
+                self.actions
+                    .push(Action::ReplaceFolder(barcode.to_string()));
+                self.actions
+                    .push(Action::WriteManifest(barcode.to_string()));
+            }
+            None => {
+                self.actions.push(Action::CreateFolder(name.to_string()));
+                self.actions.push(Action::WriteManifest(name.to_string()));
+            }
+        }
+    }
+
+    /// delete an installed pallet folder and its manifest
+    pub fn remove(&mut self, barcode: &str) {
+        self.actions.push(Action::DeleteFolder(barcode.to_string()));
+        self.actions
+            .push(Action::DeleteManifest(barcode.to_string()));
+    }
+
+    /// bytes every download in the plan fetches
+    pub fn download_size(&self) -> u64 {
+        self.actions
+            .iter()
+            .map(|x| match x {
+                Action::Download { size, .. } => *size,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// space the extracted downloads need in the mods folder
+    pub fn unpacked_size(&self) -> u64 {
+        self.actions
+            .iter()
+            .map(|x| match x {
+                Action::Download { unpacked, .. } => *unpacked,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// filesystems without room for the plan and the bytes it needs on them,
+    /// the zips go to the cache and are extracted into the mods folder, which may share a filesystem
+    pub fn short_disks(&self, mods: &str, cache: &str) -> Vec<(Disk, u64)> {
+        let mut disks: Vec<(Disk, u64)> = Vec::new();
+        for (path, needed) in [(cache, self.download_size()), (mods, self.unpacked_size())] {
+            let Some(disk) = disk(path) else {
+                continue;
+            };
+            match disks.iter_mut().find(|x| x.0.mount == disk.mount) {
+                Some(x) => x.1 += needed,
+                None => disks.push((disk, needed)),
+            }
+        }
+        disks.retain(|(disk, needed)| *needed > disk.free);
+        disks
+    }
+}
+
+/// one action per line, with the total download size
+impl std::fmt::Display for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for action in &self.actions {
+            match action {
+                Action::Subscribe(x) => writeln!(f, "  subscribe to {}", x)?,
+                Action::Unsubscribe(x) => writeln!(f, "  unsubscribe from {}", x)?,
+                Action::Download { name, size, .. } => {
+                    writeln!(f, "  download {} ({})", name, format_size(*size))?
+                }
+                Action::CreateFolder(x) => writeln!(f, "  create the pallet folder of {}", x)?,
+                Action::ReplaceFolder(x) => writeln!(f, "  replace the pallet folder {}", x)?,
+                Action::DeleteFolder(x) => writeln!(f, "  delete the pallet folder {}", x)?,
+                Action::Archive(x) => writeln!(f, "  archive {}", x)?,
+                Action::WriteManifest(x) => writeln!(f, "  write the manifest of {}", x)?,
+                Action::DeleteManifest(x) => writeln!(f, "  delete the manifest of {}", x)?,
+                Action::Upload(x) => writeln!(f, "  upload {}", x)?,
+                Action::Edit(x) => writeln!(f, "  change {}", x)?,
+                Action::CacheThumbnail(x) => writeln!(f, "  download the logo of {}", x)?,
+            }
+        }
+        let size = self.download_size();
+        if size > 0 {
+            writeln!(
+                f,
+                "  {} to download, {} once extracted",
+                format_size(size),
+                format_size(self.unpacked_size())
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// a filesystem as df reports it
+pub struct Disk {
+    /// where it is mounted
+    pub mount: String,
+    /// free bytes
+    pub free: u64,
+}
+
+/// the filesystem holding a folder, or its closest existing parent, none if df does not work here
+pub fn disk(path: &str) -> Option<Disk> {
+    let mut path = Path::new(path);
+    while !path.exists() {
+        path = path.parent()?;
+    }
+    let output = Command::new("df").arg("-Pk").arg(path).output().ok()?;
+    let output = String::from_utf8(output.stdout).ok()?;
+    let fields: Vec<&str> = output.lines().nth(1)?.split_whitespace().collect();
+    let available: u64 = fields.get(3)?.parse().ok()?;
+    Some(Disk {
+        mount: fields.get(5..)?.join(" "),
+        free: available * 1024,
+    })
+}
@@ -0,0 +1,159 @@
+//! making the mods folder match a profile, a named set of mod ids and barcodes
+
+use std::path::PathBuf;
+
+use indicatif::ProgressBar;
+use modio::{Modio, mods::Mod, types::id::Id};
+
+use crate::client::BONELAB;
+use crate::installer::{PlannedInstall, install_plan, install_planned, remove_mod};
+use crate::manifest::{InstalledMod, save_manifest};
+use crate::plan::{Action, Plan};
+use crate::state::State;
+use crate::structs::Platform;
+
+/// what activating a profile changes, see `plan_activation`
+pub struct Activation<'a> {
+    /// disabled mods in the profile, or needed by a mod in it
+    pub enable: Vec<&'a InstalledMod>,
+    /// active mods that are not in the profile
+    pub disable: Vec<&'a InstalledMod>,
+    /// mods that are not in the profile, instead of disabling them
+    pub remove: Vec<&'a InstalledMod>,
+    /// mod ids in the profile that are not installed
+    pub install: Vec<u64>,
+    /// entries that are not installed and are not mod ids either
+    pub unknown: Vec<String>,
+}
+
+impl Activation<'_> {
+    /// true when the mods folder already matches the profile
+    pub fn is_empty(&self) -> bool {
+        self.enable.is_empty()
+            && self.disable.is_empty()
+            && self.remove.is_empty()
+            && self.install.is_empty()
+    }
+
+    /// every change, with the installs `plan_installs` made for `install`
+    pub fn plan(&self, installs: &[PlannedInstall]) -> Plan {
+        let mut plan = install_plan(installs);
+        for mod_ in &self.enable {
+            plan.actions.push(Action::WriteManifest(format!(
+                "{} (enable)",
+                mod_.barcode()
+            )));
+        }
+        for mod_ in &self.disable {
+            plan.actions.push(Action::WriteManifest(format!(
+                "{} (disable)",
+                mod_.barcode()
+            )));
+        }
+        for mod_ in &self.remove {
+            plan.remove(mod_.barcode());
+        }
+        plan
+    }
+}
+
+/// compare a profile with the mods folder, mods the profile's mods depend on are kept too,
+/// local mods are matched by barcode
+pub fn plan_activation<'a>(
+    profile: &[String],
+    installed_mods: &'a [InstalledMod],
+    local_mods: &'a [InstalledMod],
+    state: &State,
+    uninstall: bool,
+) -> Activation<'a> {
+    let mut activation = Activation {
+        enable: Vec::new(),
+        disable: Vec::new(),
+        remove: Vec::new(),
+        install: Vec::new(),
+        unknown: Vec::new(),
+    };
+    // mods in the profile and everything they depend on
+    let mut wanted = Vec::new();
+    for key in profile {
+        match installed_mods
+            .iter()
+            .chain(local_mods)
+            .find(|x| x.matches(key))
+        {
+            Some(x) => wanted.extend(x.mod_id()),
+            None => match key.parse::<u64>() {
+                Ok(id) => activation.install.push(id),
+                Err(_) => activation.unknown.push(key.clone()),
+            },
+        }
+    }
+    let mut i = 0;
+    while i < wanted.len() {
+        for dependency in state.dependencies.get(&wanted[i]).into_iter().flatten() {
+            if !wanted.contains(dependency) {
+                wanted.push(*dependency);
+            }
+        }
+        i += 1;
+    }
+    for mod_ in installed_mods.iter().chain(local_mods) {
+        let keep = profile.iter().any(|key| mod_.matches(key))
+            || mod_.mod_id().is_some_and(|id| wanted.contains(&id));
+        if keep {
+            if !mod_.manifest.pallet().active {
+                activation.enable.push(mod_);
+            }
+        } else if uninstall {
+            activation.remove.push(mod_);
+        } else if mod_.manifest.pallet().active {
+            activation.disable.push(mod_);
+        }
+    }
+    activation
+}
+
+/// get the mods of a profile that are not installed, returns the ids that could not be found and why
+pub async fn fetch_profile_mods(modio: &Modio, ids: &[u64]) -> (Vec<Mod>, Vec<(u64, String)>) {
+    let mut found = Vec::new();
+    let mut missing = Vec::new();
+    for id in ids {
+        match modio.mod_(Id::new(BONELAB), Id::new(*id)).get().await {
+            Ok(x) => found.push(x),
+            Err(x) => missing.push((*id, x.to_string())),
+        }
+    }
+    (found, missing)
+}
+
+/// enable, disable and remove mods, then install the missing ones,
+/// returns the names of the installs that failed and why
+pub async fn apply_activation(
+    modio: Option<&Modio>,
+    path: &str,
+    activation: Activation<'_>,
+    installs: Vec<PlannedInstall>,
+    state: &mut State,
+    platform: Platform,
+    pb: &ProgressBar,
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    for mod_ in activation.enable {
+        let mut manifest = mod_.manifest.clone();
+        manifest.pallet_mut().active = true;
+        save_manifest(PathBuf::from(path), &manifest)?;
+    }
+    for mod_ in activation.disable {
+        let mut manifest = mod_.manifest.clone();
+        manifest.pallet_mut().active = false;
+        save_manifest(PathBuf::from(path), &manifest)?;
+    }
+    for mod_ in activation.remove {
+        remove_mod(path, mod_)?;
+    }
+    match modio {
+        Some(modio) => Ok(install_planned(modio, path, installs, state, platform, pb)
+            .await
+            .failed),
+        None => Ok(Vec::new()),
+    }
+}
@@ -0,0 +1,314 @@
+//! uploading modfiles and editing the mod pages of mods you maintain
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use modio::{Modio, files::File, mods::Mod, types::id::Id};
+
+use crate::client::{BONELAB, MODIO_API, access_token};
+use crate::installer::{PalletBuild, md5_file, pack, pallet_build};
+use crate::plan::{Action, Plan};
+use crate::structs::Platform;
+use crate::{format_size, throw};
+
+/// what `upload` sends along with the zip
+pub struct Upload<'a> {
+    /// version of the new modfile
+    pub version: &'a str,
+    /// changelog of the new modfile
+    pub changelog: &'a Option<String>,
+    /// platforms the modfile is for, pc if empty
+    pub platforms: &'a [Platform],
+    /// make the new modfile the live one
+    pub live: bool,
+}
+
+/// a zip ready to upload, see `prepare_upload`
+pub struct PreparedUpload {
+    /// the mod the modfile is added to
+    pub online_mod: Mod,
+    /// the zip, packed into the temp folder when the source was a pallet folder
+    pub zip: PathBuf,
+    /// bytes of the zip
+    pub size: u64,
+    /// md5 of the zip, mod.io checks it
+    pub md5: String,
+    /// platforms the modfile is for
+    pub platforms: Vec<Platform>,
+    /// the pallet folder that was packed, none when the source was a zip
+    pub build: Option<PalletBuild>,
+    /// the upload
+    pub plan: Plan,
+}
+
+/// pack a pallet folder if needed and describe the upload
+pub async fn prepare_upload(
+    modio: &Modio,
+    source: &Path,
+    mod_id: u64,
+    upload: &Upload<'_>,
+) -> Result<PreparedUpload, Box<dyn std::error::Error>> {
+    let online_mod = modio.mod_(Id::new(BONELAB), Id::new(mod_id)).get().await?;
+    let mut build = None;
+    let zip = match source.is_dir() {
+        true => {
+            let zip = env::temp_dir().join(format!("bonelab-mod-manager-{}.zip", mod_id));
+            let checked = pallet_build(source)?;
+            pack(&checked, &zip)?;
+            build = Some(checked);
+            zip
+        }
+        false => source.to_path_buf(),
+    };
+    let size = fs::metadata(&zip)?.len();
+    let md5 = md5_file(&zip.to_string_lossy())?;
+    let platforms = match upload.platforms.is_empty() {
+        true => vec![Platform::Pc],
+        false => upload.platforms.to_vec(),
+    };
+    let mut plan = Plan::default();
+    plan.actions.push(Action::Upload(format!(
+        "{} ({}) as version {} of {} for {}{}",
+        zip.display(),
+        format_size(size),
+        upload.version,
+        online_mod.name,
+        platforms
+            .iter()
+            .map(Platform::to_string)
+            .collect::<Vec<_>>()
+            .join(" and "),
+        if upload.live { ", live" } else { "" }
+    )));
+    Ok(PreparedUpload {
+        online_mod,
+        zip,
+        size,
+        md5,
+        platforms,
+        build,
+        plan,
+    })
+}
+
+/// upload a prepared zip as a new modfile with the token `login` saved, returns the new modfile
+pub async fn upload(
+    prepared: &PreparedUpload,
+    upload: &Upload<'_>,
+    xdg_config_home: &str,
+) -> Result<File, Box<dyn std::error::Error>> {
+    // the modio crate cannot tag platforms on uploads, so the form is sent directly
+    let mut form = reqwest::multipart::Form::new()
+        .text("version", upload.version.to_string())
+        .text("active", upload.live.to_string())
+        .text("filehash", prepared.md5.clone());
+    if let Some(changelog) = upload.changelog {
+        form = form.text("changelog", changelog.clone());
+    }
+    for platform in &prepared.platforms {
+        form = form.text("platforms[]", platform.modio().as_str().to_string());
+    }
+    let filename = match prepared.zip.file_name() {
+        Some(x) => x.to_string_lossy().into_owned(),
+        None => throw("Not a file")?,
+    };
+    let part = reqwest::multipart::Part::bytes(fs::read(&prepared.zip)?).file_name(filename);
+    form = form.part("filedata", part);
+    let response = reqwest::Client::new()
+        .post(format!(
+            "{}/games/{}/mods/{}/files",
+            MODIO_API, BONELAB, prepared.online_mod.id
+        ))
+        .bearer_auth(access_token(xdg_config_home)?)
+        .header("Accept", "application/json")
+        .multipart(form)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        return throw(&format!(
+            "mod.io rejected the upload ({}): {}",
+            status,
+            response.text().await?
+        ));
+    }
+    Ok(response.json().await?)
+}
+
+/// the change an edit makes
+enum EditKind {
+    Details(modio::mods::EditModOptions),
+    Tags {
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+    Logo(PathBuf),
+}
+
+/// a change to a mod page, see `plan_details_edit`, `plan_tags_edit` and `plan_logo_edit`
+pub struct ModEdit {
+    /// the mod as it is on mod.io
+    pub online_mod: Mod,
+    /// one line per changed field, empty when nothing changes
+    pub plan: Plan,
+    kind: EditKind,
+}
+
+/// new details of a mod page, fields left out are not changed
+pub struct Details<'a> {
+    /// name of the mod
+    pub name: &'a Option<String>,
+    /// summary of the mod
+    pub summary: &'a Option<String>,
+    /// description of the mod, html is allowed
+    pub description: Option<String>,
+    /// true for public, false for hidden
+    pub public: Option<bool>,
+}
+
+/// compare new details with the mod page
+pub async fn plan_details_edit(
+    modio: &Modio,
+    mod_id: u64,
+    details: Details<'_>,
+) -> Result<ModEdit, Box<dyn std::error::Error>> {
+    let online_mod = modio.mod_(Id::new(BONELAB), Id::new(mod_id)).get().await?;
+    let mut plan = Plan::default();
+    let mut options = modio::mods::EditModOptions::default();
+    if let Some(name) = details.name
+        && *name != online_mod.name
+    {
+        plan.actions.push(Action::Edit(format!(
+            "name: {} -> {}",
+            online_mod.name, name
+        )));
+        options = options.name(name);
+    }
+    if let Some(summary) = details.summary
+        && *summary != online_mod.summary
+    {
+        plan.actions.push(Action::Edit(format!(
+            "summary: {} -> {}",
+            online_mod.summary, summary
+        )));
+        options = options.summary(summary);
+    }
+    if let Some(description) = details.description {
+        let old = online_mod.description.clone().unwrap_or_default();
+        if description != old {
+            plan.actions.push(Action::Edit(format!(
+                "description: {} characters -> {} characters",
+                old.chars().count(),
+                description.chars().count()
+            )));
+            options = options.description(description);
+        }
+    }
+    if let Some(public) = details.public {
+        let was_public = online_mod.visible == modio::mods::Visibility::PUBLIC;
+        if public != was_public {
+            let name = |x| if x { "public" } else { "hidden" };
+            plan.actions.push(Action::Edit(format!(
+                "visibility: {} -> {}",
+                name(was_public),
+                name(public)
+            )));
+            options = options.visible(public);
+        }
+    }
+    Ok(ModEdit {
+        online_mod,
+        plan,
+        kind: EditKind::Details(options),
+    })
+}
+
+/// the tags to add that the mod page does not have and the tags to remove that it has
+pub async fn plan_tags_edit(
+    modio: &Modio,
+    mod_id: u64,
+    add: &[String],
+    remove: &[String],
+) -> Result<ModEdit, Box<dyn std::error::Error>> {
+    let online_mod = modio.mod_(Id::new(BONELAB), Id::new(mod_id)).get().await?;
+    let tags: Vec<&str> = online_mod.tags.iter().map(|x| x.name.as_str()).collect();
+    let add: Vec<String> = add
+        .iter()
+        .filter(|x| !tags.contains(&x.as_str()))
+        .cloned()
+        .collect();
+    let remove: Vec<String> = remove
+        .iter()
+        .filter(|x| tags.contains(&x.as_str()))
+        .cloned()
+        .collect();
+    let mut plan = Plan::default();
+    for tag in &add {
+        plan.actions.push(Action::Edit(format!("add tag {}", tag)));
+    }
+    for tag in &remove {
+        plan.actions
+            .push(Action::Edit(format!("remove tag {}", tag)));
+    }
+    Ok(ModEdit {
+        online_mod,
+        plan,
+        kind: EditKind::Tags { add, remove },
+    })
+}
+
+/// replace the logo of a mod page with an image file
+pub async fn plan_logo_edit(
+    modio: &Modio,
+    mod_id: u64,
+    file: &Path,
+) -> Result<ModEdit, Box<dyn std::error::Error>> {
+    if !file.is_file() {
+        throw(&format!("{} is not a file", file.display()))?;
+    }
+    let online_mod = modio.mod_(Id::new(BONELAB), Id::new(mod_id)).get().await?;
+    let mut plan = Plan::default();
+    plan.actions.push(Action::Edit(format!(
+        "logo: {} -> {}",
+        online_mod.logo.original,
+        file.display()
+    )));
+    Ok(ModEdit {
+        online_mod,
+        plan,
+        kind: EditKind::Logo(file.to_path_buf()),
+    })
+}
+
+/// send an edit to mod.io
+pub async fn apply_edit(modio: &Modio, edit: ModEdit) -> Result<(), Box<dyn std::error::Error>> {
+    let modref = modio.mod_(Id::new(BONELAB), edit.online_mod.id);
+    match edit.kind {
+        EditKind::Details(options) => {
+            modref.edit(options).await?;
+        }
+        EditKind::Tags { add, remove } => {
+            if !add.is_empty() {
+                modref
+                    .clone()
+                    .tags()
+                    .add(modio::mods::EditTagsOptions::new(&add))
+                    .await?;
+            }
+            if !remove.is_empty() {
+                modref
+                    .tags()
+                    .delete(modio::mods::EditTagsOptions::new(&remove))
+                    .await?;
+            }
+        }
+        EditKind::Logo(file) => {
+            modref
+                .add_media(modio::mods::AddMediaOptions::default().logo(file))
+                .await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,108 @@
+//! the local state store in `state.json`
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::Write,
+};
+
+/// local state of the mod manager, kept in ~/.config/bonelab-mod-manager/state.json
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct State {
+    /// named sets of mod ids or barcodes
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Vec<String>>,
+    /// mod ids held at a modfile id, updates skip these
+    #[serde(default)]
+    pub pins: BTreeMap<u64, u64>,
+    /// mod ids each installed mod depended on when it was installed
+    #[serde(default)]
+    pub dependencies: BTreeMap<u64, Vec<u64>>,
+    /// unix time of the last update check, later checks only look at mod.io events since then
+    #[serde(default)]
+    pub last_sync: Option<i64>,
+    /// installed mods that mod.io does not serve anymore
+    #[serde(default)]
+    pub unavailable: BTreeMap<u64, Unavailable>,
+    /// mod ids that were both installed and subscribed after the last `sync-subscriptions`
+    #[serde(default)]
+    pub subscriptions: BTreeSet<u64>,
+}
+
+/// why mod.io does not serve a mod anymore
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Availability {
+    /// the mod was deleted
+    Deleted,
+    /// the author hid the mod
+    Hidden,
+    /// the mod was not accepted by the moderators
+    NotAccepted,
+    /// the api key or token may not see the mod
+    Unauthorized,
+}
+
+impl std::fmt::Display for Availability {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let reason = match self {
+            Availability::Deleted => "deleted",
+            Availability::Hidden => "hidden",
+            Availability::NotAccepted => "not accepted",
+            Availability::Unauthorized => "unauthorized",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+/// an installed mod that mod.io does not serve anymore
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Unavailable {
+    /// barcode of the pallet in the mods folder
+    pub barcode: String,
+    /// why mod.io does not serve it
+    pub reason: Availability,
+    /// unix time the mod was first seen missing
+    pub since: i64,
+    /// the user chose to keep the local copy, so it is not reported again
+    #[serde(default)]
+    pub kept: bool,
+}
+
+impl State {
+    /// check every mod on the next update instead of only those with new events,
+    /// for when installed modfiles were changed outside of an update
+    pub fn rescan(&mut self) {
+        self.last_sync = None;
+    }
+
+    /// ids of the mods that depended on a mod when they were installed
+    pub fn dependents(&self, mod_id: u64) -> Vec<u64> {
+        self.dependencies
+            .iter()
+            .filter(|(_, deps)| deps.contains(&mod_id))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    fn path(xdg_config_home: &str) -> String {
+        xdg_config_home.to_string() + "/bonelab-mod-manager/state.json"
+    }
+
+    /// read the state file, a missing file is an empty state
+    pub fn load(xdg_config_home: &str) -> Result<State, Box<dyn std::error::Error>> {
+        match fs::read_to_string(State::path(xdg_config_home)) {
+            Ok(x) => Ok(serde_json::from_str(&x)?),
+            Err(_) => Ok(State::default()),
+        }
+    }
+
+    /// write the state file, creating its folder if needed
+    pub fn save(&self, xdg_config_home: &str) -> Result<(), Box<dyn std::error::Error>> {
+        fs::create_dir_all(xdg_config_home.to_string() + "/bonelab-mod-manager")?;
+        let mut file = File::create(State::path(xdg_config_home))?;
+        file.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        Ok(())
+    }
+}
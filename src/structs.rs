@@ -1,78 +1,514 @@
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+//! the json formats the mod manager reads and writes, `.manifest` files, pallet jsons, mod lists and lockfiles
+
+use modio::TargetPlatform;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+/// the manifest version the mod manager reads and writes, other versions are refused
+pub const MANIFEST_VERSION: u64 = 2;
+
+/// type of the pallet object a manifest's root refers to
+pub const PALLET_MANIFEST: &str = "pallet-manifest#0";
+/// type of the mod listing a pallet refers to
+pub const MOD_LISTING: &str = "mod-listing#0";
+/// type of a mod.io target of a listing
+pub const MOD_TARGET_MODIO: &str = "mod-target-modio#0";
+
+/// the platform of a mods folder, named like the targets of a mod listing
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    /// windows, also what runs under proton
+    #[default]
+    Pc,
+    /// quest
+    Android,
+}
+
+impl Platform {
+    /// every platform the mod manager knows
+    pub const ALL: [Platform; 2] = [Platform::Pc, Platform::Android];
+
+    /// key of the platform in the targets of a mod listing
+    pub fn key(self) -> &'static str {
+        match self {
+            Platform::Pc => "pc",
+            Platform::Android => "android",
+        }
+    }
+
+    /// the mod.io platform of modfiles for this platform
+    pub fn modio(self) -> TargetPlatform {
+        match self {
+            Platform::Pc => TargetPlatform::WINDOWS,
+            Platform::Android => TargetPlatform::ANDROID,
+        }
+    }
+
+    /// where the game keeps its mods, pallet and catalog paths in manifests start with it
+    pub fn game_mod_folder(self) -> &'static str {
+        match self {
+            Platform::Pc => "C:/users/steamuser/AppData/LocalLow/Stress Level Zero/BONELAB/Mods",
+            Platform::Android => {
+                "/storage/emulated/0/Android/data/com.StressLevelZero.BONELAB/files/Mods"
+            }
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.key())
+    }
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pc" | "windows" => Ok(Platform::Pc),
+            "android" | "quest" => Ok(Platform::Android),
+            x => Err(format!("unknown platform {}, use pc or android", x)),
+        }
+    }
+}
+
+/// a `.manifest` file, a graph of objects that refer to each other by key
+///
+/// deserializing checks the version and that the root is a pallet
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(try_from = "ManifestJson")]
 pub struct Manifest {
+    /// format version, `MANIFEST_VERSION` for every manifest that was read
     pub version: u64,
+    /// refers to the pallet object
     pub root: Root,
-    pub objects: Object,
+    /// objects by key, the key is what references refer to
+    pub objects: BTreeMap<String, ManifestObject>,
+    /// fields this version of the mod manager does not know, written back as they were
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// a manifest as it is in the file, before its root is checked
+#[derive(Deserialize)]
+struct ManifestJson {
+    version: u64,
+    root: Root,
+    objects: BTreeMap<String, ManifestObject>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
+impl TryFrom<ManifestJson> for Manifest {
+    type Error = String;
+
+    fn try_from(json: ManifestJson) -> Result<Self, Self::Error> {
+        check_version(json.version)?;
+        match json.objects.get(&json.root.reference) {
+            Some(ManifestObject::Pallet(_)) => Ok(Manifest {
+                version: json.version,
+                root: json.root,
+                objects: json.objects,
+                extra: json.extra,
+            }),
+            _ => Err("the root of the manifest is not a pallet".into()),
+        }
+    }
+}
+
+/// the object a manifest or pallet json starts from
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Root {
+    /// key of the object
     #[serde(rename = "ref")]
     pub reference: String,
+    /// type of the object
     #[serde(rename = "type")]
     pub type_: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-// #[serde(untagged)]
-pub struct Object {
-    #[serde(rename = "1")]
-    pub pallet: Pallet,
-    #[serde(rename = "2")]
-    pub mod_listing: Option<ModListing>,
-    #[serde(rename = "3")]
-    pub mod_target: Option<ModTarget>,
+/// an object of a manifest, picked by the type in its `isa`
+#[derive(Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ManifestObject {
+    /// a `pallet-manifest#0`
+    Pallet(Pallet),
+    /// a `mod-listing#0`
+    ModListing(ModListing),
+    /// a `mod-target-modio#0`
+    ModTarget(ModTarget),
+    /// objects of types the mod manager does not know, or that did not parse
+    Other(Value),
+}
+
+impl<'de> Deserialize<'de> for ManifestObject {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        let type_ = value
+            .get("isa")
+            .and_then(|x| x.get("type"))
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let object = match type_ {
+            PALLET_MANIFEST => serde_json::from_value(value.clone()).map(ManifestObject::Pallet),
+            MOD_LISTING => serde_json::from_value(value.clone()).map(ManifestObject::ModListing),
+            MOD_TARGET_MODIO => {
+                serde_json::from_value(value.clone()).map(ManifestObject::ModTarget)
+            }
+            _ => return Ok(ManifestObject::Other(value)),
+        };
+        Ok(object.unwrap_or(ManifestObject::Other(value)))
+    }
+}
+
+impl Manifest {
+    /// a manifest with the pallet as object "1", the listing as "2" and one object per target after that
+    pub fn new(
+        mut pallet: Pallet,
+        listing: Option<ModListing>,
+        targets: Vec<(Platform, ModTarget)>,
+    ) -> Manifest {
+        let mut objects = BTreeMap::new();
+        if let Some(mut listing) = listing {
+            pallet.modListing = Some(Reference {
+                reference: "2".into(),
+                type_: MOD_LISTING.into(),
+            });
+            for (i, (platform, target)) in targets.into_iter().enumerate() {
+                let key = (i + 3).to_string();
+                listing.targets.insert(
+                    platform.key().into(),
+                    Reference {
+                        reference: key.clone(),
+                        type_: MOD_TARGET_MODIO.into(),
+                    },
+                );
+                objects.insert(key, ManifestObject::ModTarget(target));
+            }
+            objects.insert("2".into(), ManifestObject::ModListing(listing));
+        }
+        objects.insert("1".into(), ManifestObject::Pallet(pallet));
+        Manifest {
+            version: MANIFEST_VERSION,
+            root: Root {
+                reference: "1".into(),
+                type_: PALLET_MANIFEST.into(),
+            },
+            objects,
+            extra: Map::new(),
+        }
+    }
+
+    /// parse a manifest of `MANIFEST_VERSION`, it has to have a pallet as its root
+    pub fn parse(json: &str) -> Result<Manifest, Box<dyn std::error::Error>> {
+        let value: Value = serde_json::from_str(json)?;
+        let version = match value.get("version").and_then(Value::as_u64) {
+            Some(x) => x,
+            None => return crate::throw("the manifest has no version"),
+        };
+        // checked before the rest, an unknown version may not have the fields of this one
+        if let Err(x) = check_version(version) {
+            return crate::throw(&x);
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// the pallet the root refers to
+    ///
+    /// panics if `root` or `objects` were changed so the root is not a pallet anymore
+    pub fn pallet(&self) -> &Pallet {
+        match self.objects.get(&self.root.reference) {
+            Some(ManifestObject::Pallet(x)) => x,
+            _ => panic!("the root of the manifest is not a pallet"),
+        }
+    }
+
+    /// the pallet the root refers to, panics like `pallet`
+    pub fn pallet_mut(&mut self) -> &mut Pallet {
+        match self.objects.get_mut(&self.root.reference) {
+            Some(ManifestObject::Pallet(x)) => x,
+            _ => panic!("the root of the manifest is not a pallet"),
+        }
+    }
+
+    /// the listing the pallet refers to
+    pub fn mod_listing(&self) -> Option<&ModListing> {
+        let reference = self.pallet().modListing.as_ref()?;
+        match self.objects.get(&reference.reference) {
+            Some(ManifestObject::ModListing(x)) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// the mod listing the pallet refers to, to edit it
+    pub fn mod_listing_mut(&mut self) -> Option<&mut ModListing> {
+        let reference = self.pallet().modListing.clone()?;
+        match self.objects.get_mut(&reference.reference) {
+            Some(ManifestObject::ModListing(x)) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// the mod.io target of the listing for a platform
+    pub fn mod_target(&self, platform: Platform) -> Option<&ModTarget> {
+        let reference = self.mod_listing()?.targets.get(platform.key())?;
+        match self.objects.get(&reference.reference) {
+            Some(ManifestObject::ModTarget(x)) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// the mod.io target of the listing for a platform, to edit it
+    pub fn mod_target_mut(&mut self, platform: Platform) -> Option<&mut ModTarget> {
+        let reference = self
+            .mod_listing()?
+            .targets
+            .get(platform.key())?
+            .reference
+            .clone();
+        match self.objects.get_mut(&reference) {
+            Some(ManifestObject::ModTarget(x)) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+/// refuse manifest versions other than `MANIFEST_VERSION`, the mod manager knows no older format to migrate from
+fn check_version(version: u64) -> Result<(), String> {
+    match version {
+        MANIFEST_VERSION => Ok(()),
+        x if x > MANIFEST_VERSION => Err(format!(
+            "manifest version {} is newer than version {} this mod manager knows, update bonelab-mod-manager",
+            x, MANIFEST_VERSION
+        )),
+        x => Err(format!(
+            "manifest version {} is older than any version this mod manager can read",
+            x
+        )),
+    }
 }
 
+/// the pallet object of a manifest, where the game finds the pallet
 #[allow(non_snake_case)]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Pallet {
+    /// barcode of the pallet, also the name of its folder
     pub palletBarcode: String,
+    /// path of the pallet json as the game sees it
     pub palletPath: String,
+    /// path of the catalog json as the game sees it
     pub catalogPath: String,
+    /// version of the installed modfile
     pub version: Option<String>,
+    /// unix milliseconds of the first install
     pub installedDate: String,
+    /// unix milliseconds of the last install or update
     pub updateDate: String,
+    /// the mod listing, local pallets have none
     pub modListing: Option<Reference>,
+    /// false when the game should not load the pallet
     pub active: bool,
+    /// installed from a zip or folder instead of mod.io
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub local: bool,
+    /// `pallet-manifest#0`
     pub isa: Isa,
+    /// fields the mod manager does not know
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
+/// what mod.io said about a mod when it was installed
 #[allow(non_snake_case)]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ModListing {
+    /// barcode of the pallet
     pub barcode: String,
+    /// name of the mod
     pub title: Option<String>,
+    /// summary of the mod
     pub description: Option<String>,
+    /// name of the submitter
     pub author: Option<String>,
+    /// version of the modfile
     pub version: Option<String>,
+    /// the mod logo on mod.io
     pub thumbnailUrl: Option<String>,
-    pub targets: HashMap<String, Reference>,
+    /// targets by platform, "pc" or "android"
+    pub targets: BTreeMap<String, Reference>,
+    /// `mod-listing#0`
     pub isa: Isa,
+    /// fields the mod manager does not know
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
+/// the mod.io mod and modfile a pallet was installed from
 #[allow(non_snake_case)]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ModTarget {
+    /// a local image the game shows instead of the mod.io logo
     pub thumbnailOverride: Option<String>,
+    /// mod.io game id, always bonelab
     pub gameId: u64,
+    /// mod.io mod id
     pub modId: u64,
+    /// mod.io modfile id
     pub modfileId: u64,
+    /// `mod-target-modio#0`
     pub isa: Isa,
+    /// fields the mod manager does not know
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
+/// a reference from one object to another by key
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Reference {
+    /// key of the object
     #[serde(rename = "ref")]
     pub reference: String,
+    /// type of the object
     #[serde(rename = "type")]
     pub type_: String,
 }
 
+/// the type of an object
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Isa {
+    /// type name, like `pallet-manifest#0`
     #[serde(rename = "type")]
     pub type_: String,
 }
+
+/// the `*.pallet.json` inside a pallet folder
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PalletJson {
+    /// format version
+    pub version: u64,
+    /// refers to the pallet object
+    pub root: Root,
+    /// objects by key
+    pub objects: HashMap<String, PalletObject>,
+    /// types used by the objects
+    #[serde(default)]
+    pub types: HashMap<String, Isa>,
+}
+
+/// any object in a pallet json, only the fields the mod manager needs are read
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PalletObject {
+    /// barcode of the object
+    pub barcode: Option<String>,
+    /// title shown in the game
+    pub title: Option<String>,
+    /// author shown in the game
+    pub author: Option<String>,
+    /// version of the pallet
+    pub version: Option<String>,
+    /// pallets this one needs
+    #[serde(default)]
+    pub dependencies: Vec<PalletDependency>,
+    /// type of the object
+    pub isa: Option<Isa>,
+}
+
+/// pallet dependencies are references to other objects, or inline barcodes
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum PalletDependency {
+    /// a reference to an object of the same pallet json
+    Reference(Reference),
+    /// an object with a barcode
+    Inline {
+        /// barcode of the pallet
+        barcode: String,
+    },
+    /// a barcode
+    Barcode(String),
+}
+
+impl PalletJson {
+    /// the pallet object the root refers to
+    pub fn pallet(&self) -> Option<&PalletObject> {
+        self.objects.get(&self.root.reference)
+    }
+
+    /// barcode of the root pallet
+    pub fn barcode(&self) -> Option<&str> {
+        self.pallet().and_then(|x| x.barcode.as_deref())
+    }
+
+    /// barcodes of the pallets this pallet depends on
+    pub fn dependencies(&self) -> Vec<String> {
+        let pallet = match self.pallet() {
+            Some(x) => x,
+            None => return Vec::new(),
+        };
+        pallet
+            .dependencies
+            .iter()
+            .filter_map(|x| match x {
+                PalletDependency::Reference(x) => self
+                    .objects
+                    .get(&x.reference)
+                    .and_then(|x| x.barcode.clone()),
+                PalletDependency::Inline { barcode } => Some(barcode.clone()),
+                PalletDependency::Barcode(x) => Some(x.clone()),
+            })
+            .collect()
+    }
+}
+
+/// one mod in a modlist file made by `export`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ModlistEntry {
+    /// mod.io mod id
+    pub mod_id: u64,
+    /// mod.io modfile id
+    pub modfile_id: u64,
+    /// version of the modfile
+    pub version: Option<String>,
+    /// barcode of the pallet
+    pub barcode: String,
+    /// platform of the mods folder it was exported from, modlists made before android support are for pc
+    #[serde(default)]
+    pub platform: Platform,
+}
+
+/// the mods `sync` should install, with optional semver requirements
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DesiredMods {
+    /// the mods, in no particular order
+    pub mods: Vec<DesiredMod>,
+}
+
+/// one mod `sync` should install
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DesiredMod {
+    /// mod.io mod id
+    pub mod_id: u64,
+    /// semver requirement the modfile version has to match
+    pub version: Option<String>,
+}
+
+/// an exact modfile resolved by `sync`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LockedMod {
+    /// mod.io mod id
+    pub mod_id: u64,
+    /// mod.io modfile id
+    pub modfile_id: u64,
+    /// version of the modfile
+    pub version: Option<String>,
+    /// md5 of the modfile zip, checked before it is installed
+    pub md5: String,
+    /// locks made before android support are for pc
+    #[serde(default)]
+    pub platform: Platform,
+}
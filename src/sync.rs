@@ -0,0 +1,580 @@
+//! making the mods folder match a modlist, a desired mods file or the mod.io subscriptions
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::Write,
+    path::Path,
+};
+
+use indicatif::ProgressBar;
+use modio::{Modio, mods::Mod, types::id::Id};
+
+use crate::client::{
+    BONELAB, is_for, resolve_modfile, set_subscribed, subscribed_mods, version_matches,
+};
+use crate::installer::{
+    DownloadOptions, InstallPlan, download_mod, install_plan, install_planned, plan_installs,
+    remove_mod,
+};
+use crate::manifest::InstalledMod;
+use crate::plan::{Action, Plan};
+use crate::state::State;
+use crate::structs::{DesiredMods, LockedMod, ModlistEntry, Platform};
+use crate::{BMMError, throw};
+
+/// an exact modfile to install, over an installed copy if there is one
+pub struct ModInstall<'a> {
+    /// the mod on mod.io
+    pub online_mod: Mod,
+    /// the modfile to install
+    pub modfile: modio::files::File,
+    /// the copy it replaces
+    pub installed: Option<&'a InstalledMod>,
+}
+
+/// install the modfile of a `ModInstall`, keeping the install date of the copy it replaces
+pub async fn install_modfile(
+    modio: &Modio,
+    path: &str,
+    install: &ModInstall<'_>,
+    platform: Platform,
+) -> Result<(), Box<dyn std::error::Error>> {
+    download_mod(
+        &install.online_mod,
+        modio,
+        Path::new(path),
+        platform,
+        DownloadOptions {
+            modfile: Some(&install.modfile),
+            manifest: None,
+            installed_date: install
+                .installed
+                .and_then(|x| x.manifest.pallet().installedDate.parse().ok()),
+        },
+    )
+    .await
+}
+
+/// the installed mods and their exact modfiles, sorted by barcode
+pub fn modlist(installed_mods: &[InstalledMod]) -> Vec<ModlistEntry> {
+    let mut modlist: Vec<ModlistEntry> = installed_mods
+        .iter()
+        .map(|mod_| {
+            let target = mod_.target();
+            ModlistEntry {
+                mod_id: target.modId,
+                modfile_id: target.modfileId,
+                version: mod_.manifest.pallet().version.clone(),
+                barcode: mod_.barcode().to_string(),
+                platform: mod_.platform,
+            }
+        })
+        .collect();
+    modlist.sort_by(|a, b| a.barcode.cmp(&b.barcode));
+    modlist
+}
+
+/// read a modlist file made by `write_modlist`
+pub fn read_modlist(file: &Path) -> Result<Vec<ModlistEntry>, Box<dyn std::error::Error>> {
+    Ok(serde_json::from_str(&fs::read_to_string(file)?)?)
+}
+
+/// write a modlist file
+pub fn write_modlist(
+    file: &Path,
+    modlist: &[ModlistEntry],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut out = File::create(file)?;
+    out.write_all(serde_json::to_string_pretty(modlist)?.as_bytes())?;
+    Ok(())
+}
+
+/// the installs that make the mods folder match a modlist, see `plan_import`
+pub struct Import<'a> {
+    /// the downloads and folders to replace
+    pub plan: Plan,
+    /// the modfiles to install
+    pub installs: Vec<ModInstall<'a>>,
+    /// entries mod.io does not serve anymore or that are for another platform, and why
+    pub unavailable: Vec<(ModlistEntry, String)>,
+}
+
+/// look up the modlist entries that are not installed at their modfile,
+/// entries exported from another platform or with modfiles not for the platform are refused
+pub async fn plan_import<'a>(
+    modio: &Modio,
+    modlist: &[ModlistEntry],
+    installed_mods: &'a [InstalledMod],
+    platform: Platform,
+) -> Result<Import<'a>, Box<dyn std::error::Error>> {
+    let mut import = Import {
+        plan: Plan::default(),
+        installs: Vec::new(),
+        unavailable: Vec::new(),
+    };
+    for entry in modlist {
+        let installed = installed_mods
+            .iter()
+            .find(|x| x.mod_id() == Some(entry.mod_id));
+        if installed.is_some_and(|x| x.target().modfileId == entry.modfile_id) {
+            continue;
+        }
+        if entry.platform != platform {
+            import.unavailable.push((
+                entry.clone(),
+                format!("exported for {}, not {}", entry.platform, platform),
+            ));
+            continue;
+        }
+        let modref = modio.mod_(Id::new(BONELAB), Id::new(entry.mod_id));
+        let online_mod = match modref.clone().get().await {
+            Ok(x) => x,
+            Err(x) => {
+                import.unavailable.push((entry.clone(), x.to_string()));
+                continue;
+            }
+        };
+        let modfile = match modref.file(Id::new(entry.modfile_id)).get().await {
+            Ok(x) if is_for(&x, platform) => x,
+            Ok(_) => {
+                import.unavailable.push((
+                    entry.clone(),
+                    format!("the modfile is not for {}", platform),
+                ));
+                continue;
+            }
+            Err(x) => {
+                import.unavailable.push((entry.clone(), x.to_string()));
+                continue;
+            }
+        };
+        import.plan.install(
+            &online_mod.name,
+            modfile.filesize,
+            modfile.filesize_uncompressed,
+            installed.map(|x| x.barcode()),
+        );
+        import.installs.push(ModInstall {
+            online_mod,
+            modfile,
+            installed,
+        });
+    }
+    Ok(import)
+}
+
+/// read a desired mods file
+pub fn read_desired(file: &Path) -> Result<DesiredMods, Box<dyn std::error::Error>> {
+    Ok(serde_json::from_str(&fs::read_to_string(file)?)?)
+}
+
+/// read a lockfile, a missing one is empty
+pub fn read_lockfile(lockfile: &Path) -> Result<Vec<LockedMod>, Box<dyn std::error::Error>> {
+    match fs::read_to_string(lockfile) {
+        Ok(x) => Ok(serde_json::from_str(&x)?),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+/// write the exact modfiles `sync` resolved, keeping what is locked for the other platforms
+pub fn write_lockfile(
+    lockfile: &Path,
+    lock: &[LockedMod],
+    old_lock: &[LockedMod],
+    platform: Platform,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut all: Vec<&LockedMod> = old_lock
+        .iter()
+        .filter(|x| x.platform != platform)
+        .chain(lock)
+        .collect();
+    all.sort_by_key(|x| (x.mod_id, x.platform.key()));
+    let mut out = File::create(lockfile)?;
+    out.write_all(serde_json::to_string_pretty(&all)?.as_bytes())?;
+    Ok(())
+}
+
+/// resolve the desired mods into exact modfiles for a platform, sorted by mod id
+///
+/// locks that still match their requirement are kept unless `update` is set,
+/// with `locked` every mod has to be locked already and nothing else may be locked for the platform
+pub async fn resolve_lock(
+    modio: &Modio,
+    desired: &DesiredMods,
+    old_lock: &[LockedMod],
+    locked: bool,
+    update: bool,
+    platform: Platform,
+) -> Result<Vec<LockedMod>, Box<dyn std::error::Error>> {
+    let mut lock = Vec::new();
+    for wanted in &desired.mods {
+        let requirement = match &wanted.version {
+            Some(x) => Some(semver::VersionReq::parse(x)?),
+            None => None,
+        };
+        let old = old_lock
+            .iter()
+            .find(|x| x.mod_id == wanted.mod_id && x.platform == platform);
+        if let Some(old) = old
+            && (locked || !update)
+            && version_matches(&requirement, &old.version)
+        {
+            lock.push(old.clone());
+            continue;
+        }
+        if locked {
+            throw(&format!(
+                "mod {} is not locked for {} or its lock does not match the desired mods file",
+                wanted.mod_id, platform
+            ))?;
+        }
+        let modfile = resolve_modfile(modio, wanted.mod_id, &requirement, platform).await?;
+        lock.push(LockedMod {
+            mod_id: wanted.mod_id,
+            modfile_id: modfile.id.into(),
+            version: modfile.version.clone(),
+            md5: modfile.filehash.md5.clone(),
+            platform,
+        });
+    }
+    lock.sort_by_key(|x| x.mod_id);
+    if locked && lock.len() != old_lock.iter().filter(|x| x.platform == platform).count() {
+        throw(&format!(
+            "lockfile has mods for {} that are not in the desired mods file",
+            platform
+        ))?;
+    }
+    Ok(lock)
+}
+
+/// the changes that make the mods folder match a lock, see `plan_sync`
+pub struct SyncPlan<'a> {
+    /// the downloads, replaced and deleted folders
+    pub plan: Plan,
+    /// dependencies of the locked mods that are neither installed nor locked,
+    /// installed at their live modfile before the locked mods
+    pub dependencies: InstallPlan,
+    /// the locked modfiles that are not installed
+    pub installs: Vec<ModInstall<'a>>,
+    /// mod ids of the locked mods to install and the mods they depend on
+    pub depends_on: BTreeMap<u64, Vec<u64>>,
+    /// installed mods that are not locked and no locked mod needs
+    pub remove: Vec<&'a InstalledMod>,
+}
+
+/// the locked mods and every mod they depend on, directly or through other dependencies
+pub fn needed_mods(locked: &[u64], dependencies: &BTreeMap<u64, Vec<u64>>) -> BTreeSet<u64> {
+    let mut needed: BTreeSet<u64> = locked.iter().copied().collect();
+    let mut queue = locked.to_vec();
+    while let Some(id) = queue.pop() {
+        for dependency in dependencies.get(&id).into_iter().flatten() {
+            if needed.insert(*dependency) {
+                queue.push(*dependency);
+            }
+        }
+    }
+    needed
+}
+
+/// find the locked modfiles to install, the dependencies they are missing and the installed
+/// mods to remove, modfiles that changed on mod.io or are not for the platform are refused
+///
+/// installed mods a locked mod depends on, going by `state.dependencies`, are kept
+pub async fn plan_sync<'a>(
+    modio: &Modio,
+    lock: &[LockedMod],
+    installed_mods: &'a [InstalledMod],
+    state: &State,
+    platform: Platform,
+) -> Result<SyncPlan<'a>, Box<dyn std::error::Error>> {
+    let mut sync = SyncPlan {
+        plan: Plan::default(),
+        dependencies: InstallPlan::default(),
+        installs: Vec::new(),
+        depends_on: BTreeMap::new(),
+        remove: Vec::new(),
+    };
+    let locked_ids: Vec<u64> = lock.iter().map(|x| x.mod_id).collect();
+    let mut missing = Vec::new();
+    for locked_mod in lock {
+        let installed = installed_mods
+            .iter()
+            .find(|x| x.mod_id() == Some(locked_mod.mod_id));
+        if installed.is_some_and(|x| x.target().modfileId == locked_mod.modfile_id) {
+            continue;
+        }
+        let modref = modio.mod_(Id::new(BONELAB), Id::new(locked_mod.mod_id));
+        let online_mod = modref.clone().get().await?;
+        let modfile = modref
+            .clone()
+            .file(Id::new(locked_mod.modfile_id))
+            .get()
+            .await?;
+        if modfile.filehash.md5 != locked_mod.md5 {
+            throw(&format!(
+                "modfile {} of mod {} changed on mod.io since it was locked",
+                locked_mod.modfile_id, locked_mod.mod_id
+            ))?;
+        }
+        if !is_for(&modfile, platform) {
+            throw(&format!(
+                "modfile {} of mod {} is not for {}",
+                locked_mod.modfile_id, locked_mod.mod_id, platform
+            ))?;
+        }
+        let mut dependencies = Vec::new();
+        if online_mod.dependencies {
+            for dependency in modref.dependencies().list().await? {
+                let dep_id = dependency.mod_id.get();
+                dependencies.push(dep_id);
+                if !locked_ids.contains(&dep_id)
+                    && !missing.contains(&dep_id)
+                    && !installed_mods.iter().any(|x| x.mod_id() == Some(dep_id))
+                {
+                    missing.push(dep_id);
+                }
+            }
+        }
+        sync.depends_on.insert(locked_mod.mod_id, dependencies);
+        let name = format!(
+            "{} {}",
+            online_mod.name,
+            locked_mod.version.clone().unwrap_or_default()
+        );
+        sync.plan.install(
+            &name,
+            modfile.filesize,
+            modfile.filesize_uncompressed,
+            installed.map(|x| x.barcode()),
+        );
+        sync.installs.push(ModInstall {
+            online_mod,
+            modfile,
+            installed,
+        });
+    }
+
+    let mut dependency_mods = Vec::new();
+    for id in missing {
+        dependency_mods.push(modio.mod_(Id::new(BONELAB), Id::new(id)).get().await?);
+    }
+    sync.dependencies = plan_installs(modio, dependency_mods, installed_mods, platform).await?;
+    sync.plan
+        .actions
+        .splice(0..0, install_plan(&sync.dependencies.installs).actions);
+
+    let mut dependencies = state.dependencies.clone();
+    dependencies.extend(sync.depends_on.clone());
+    let needed = needed_mods(&locked_ids, &dependencies);
+    for mod_ in installed_mods {
+        if mod_.mod_id().is_some_and(|x| !needed.contains(&x)) {
+            sync.plan.remove(mod_.barcode());
+            sync.remove.push(mod_);
+        }
+    }
+    Ok(sync)
+}
+
+/// remove the mods a `SyncPlan` drops, install the missing dependencies and the locked modfiles,
+/// and remember what each installed mod depends on, returns the installs that failed and why
+pub async fn apply_sync(
+    modio: &Modio,
+    path: &str,
+    sync: SyncPlan<'_>,
+    state: &mut State,
+    platform: Platform,
+    pb: &ProgressBar,
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    for mod_ in &sync.remove {
+        remove_mod(path, mod_)?;
+        state.dependencies.remove(&mod_.target().modId);
+    }
+    let mut failed = install_planned(modio, path, sync.dependencies.installs, state, platform, pb)
+        .await
+        .failed;
+    pb.reset();
+    pb.set_length(sync.installs.len() as u64);
+    for install in &sync.installs {
+        pb.inc(1);
+        pb.set_message(install.online_mod.name.clone());
+        match install_modfile(modio, path, install, platform).await {
+            Ok(_) => {
+                let id = install.online_mod.id.get();
+                if let Some(dependencies) = sync.depends_on.get(&id) {
+                    state.dependencies.insert(id, dependencies.clone());
+                }
+            }
+            Err(x) => failed.push((install.online_mod.name.clone(), x.to_string())),
+        }
+    }
+    pb.finish_and_clear();
+    Ok(failed)
+}
+
+/// which side `sync-subscriptions` treats as the source of truth
+#[derive(Clone, Copy, PartialEq)]
+pub enum Direction {
+    /// subscribe to installed mods, unsubscribe from mods that are not installed
+    LocalToRemote,
+    /// install subscribed mods, remove mods that are not subscribed
+    RemoteToLocal,
+    /// carry additions and removals from either side over to the other
+    Mirror,
+}
+
+impl std::str::FromStr for Direction {
+    type Err = BMMError;
+    fn from_str(s: &str) -> std::result::Result<Direction, BMMError> {
+        match s {
+            "local-to-remote" => Ok(Direction::LocalToRemote),
+            "remote-to-local" => Ok(Direction::RemoteToLocal),
+            "mirror" => Ok(Direction::Mirror),
+            _ => Err(BMMError(format!(
+                "{} is not local-to-remote, remote-to-local or mirror",
+                s
+            ))),
+        }
+    }
+}
+
+/// the changes that make subscriptions and the mods folder match, see `plan_subscription_sync`
+pub struct SubscriptionSync<'a> {
+    /// every change, in the order `apply_subscription_sync` makes them
+    pub plan: Plan,
+    /// installed mods to subscribe to
+    pub subscribe: Vec<&'a InstalledMod>,
+    /// subscribed mods to unsubscribe from
+    pub unsubscribe: Vec<Mod>,
+    /// installed mods to remove
+    pub remove: Vec<&'a InstalledMod>,
+    /// subscribed mods to install, with their dependencies
+    pub installs: InstallPlan,
+    /// mod ids that are subscribed
+    pub remote: BTreeSet<u64>,
+    /// mod ids that are both installed and subscribed already
+    pub synced: BTreeSet<u64>,
+}
+
+/// compare the subscriptions with the mods folder
+///
+/// in a mirror, `state.subscriptions` tells a mod that was removed on one side
+/// from a mod that was added on the other
+pub async fn plan_subscription_sync<'a>(
+    modio: &Modio,
+    direction: Direction,
+    installed_mods: &'a [InstalledMod],
+    state: &State,
+    platform: Platform,
+) -> Result<SubscriptionSync<'a>, Box<dyn std::error::Error>> {
+    let subscriptions = subscribed_mods(modio).await?;
+    let remote: BTreeSet<u64> = subscriptions.iter().map(|x| x.id.get()).collect();
+    let local: BTreeSet<u64> = installed_mods.iter().filter_map(|x| x.mod_id()).collect();
+    // mods installed only because another mod needs them are never subscribed
+    let needed: BTreeSet<u64> = state.dependencies.values().flatten().copied().collect();
+
+    let mut subscribe = Vec::new();
+    let mut unsubscribe = Vec::new();
+    let mut install = Vec::new();
+    let mut remove = Vec::new();
+    for mod_ in installed_mods {
+        let id = match mod_.mod_id() {
+            Some(x) => x,
+            None => continue,
+        };
+        // unavailable mods are handled by the `unavailable` command
+        if remote.contains(&id) || needed.contains(&id) || state.unavailable.contains_key(&id) {
+            continue;
+        }
+        // in a mirror, a synced mod missing from mod.io was unsubscribed on the website
+        let removed_remotely = state.subscriptions.contains(&id);
+        match direction {
+            Direction::LocalToRemote => subscribe.push(mod_),
+            Direction::RemoteToLocal => remove.push(mod_),
+            Direction::Mirror if removed_remotely => remove.push(mod_),
+            Direction::Mirror => subscribe.push(mod_),
+        }
+    }
+    for mod_ in subscriptions {
+        let id = mod_.id.get();
+        if local.contains(&id) {
+            continue;
+        }
+        // and a synced mod missing from the mods folder was deleted locally
+        let removed_locally = state.subscriptions.contains(&id);
+        match direction {
+            Direction::LocalToRemote => unsubscribe.push(mod_),
+            Direction::RemoteToLocal => install.push(mod_),
+            Direction::Mirror if removed_locally => unsubscribe.push(mod_),
+            Direction::Mirror => install.push(mod_),
+        }
+    }
+
+    let mut plan = Plan::default();
+    for mod_ in &subscribe {
+        plan.actions
+            .push(Action::Subscribe(mod_.barcode().to_string()));
+    }
+    for mod_ in &unsubscribe {
+        plan.actions.push(Action::Unsubscribe(mod_.name.clone()));
+    }
+    for mod_ in &remove {
+        plan.remove(mod_.barcode());
+    }
+    let installs = plan_installs(modio, install, installed_mods, platform).await?;
+    plan.actions
+        .extend(install_plan(&installs.installs).actions);
+    let synced = local.intersection(&remote).copied().collect();
+    Ok(SubscriptionSync {
+        plan,
+        subscribe,
+        unsubscribe,
+        remove,
+        installs,
+        remote,
+        synced,
+    })
+}
+
+/// make the changes of a `SubscriptionSync` and remember which mods are synced,
+/// returns what could not be done
+pub async fn apply_subscription_sync(
+    modio: &Modio,
+    path: &str,
+    sync: SubscriptionSync<'_>,
+    state: &mut State,
+    platform: Platform,
+    pb: &ProgressBar,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut problems = Vec::new();
+    let mut synced = sync.synced;
+    for mod_ in sync.subscribe {
+        let id = mod_.target().modId;
+        match set_subscribed(modio, id, true).await {
+            Ok(_) => {
+                synced.insert(id);
+            }
+            Err(x) => problems.push(format!("could not subscribe to {}: {}", mod_.barcode(), x)),
+        }
+    }
+    for mod_ in sync.unsubscribe {
+        if let Err(x) = set_subscribed(modio, mod_.id.get(), false).await {
+            problems.push(format!("could not unsubscribe from {}: {}", mod_.name, x));
+        }
+    }
+    for mod_ in sync.remove {
+        remove_mod(path, mod_)?;
+        state.dependencies.remove(&mod_.target().modId);
+    }
+    let outcome = install_planned(modio, path, sync.installs.installs, state, platform, pb).await;
+    for (name, x) in outcome.failed {
+        problems.push(format!("could not install {}: {}", name, x));
+    }
+    for id in outcome.installed {
+        if sync.remote.contains(&id) {
+            synced.insert(id);
+        }
+    }
+    state.subscriptions = synced;
+    Ok(problems)
+}
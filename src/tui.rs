@@ -0,0 +1,733 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::PathBuf,
+    time::Duration,
+};
+
+use indicatif::ProgressBar;
+use modio::filter::prelude::*;
+use modio::{Modio, mods::Mod, mods::filters::GameId, types::id::Id};
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, List, ListItem, ListState, Paragraph, Wrap},
+};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+
+use bonelab_mod_manager::client::BONELAB;
+use bonelab_mod_manager::format_size;
+use bonelab_mod_manager::installer::{install_planned, plan_installs, remove_mod};
+use bonelab_mod_manager::manifest::{InstalledMod, read_installed_mods, save_manifest};
+use bonelab_mod_manager::state::State;
+use bonelab_mod_manager::structs::Platform;
+use bonelab_mod_manager::updater::{install_update, pending_update};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Pane {
+    Installed,
+    Subscriptions,
+    Search,
+}
+
+/// what background tasks report back to the ui
+enum Msg {
+    Progress(usize, String),
+    Done(usize, String),
+    Subscriptions(Vec<Mod>),
+    Search(Vec<Mod>),
+    Dependencies(u64, Vec<u64>),
+    /// mods a task installed and the mod ids each depended on
+    Installed(Vec<(u64, Vec<u64>)>),
+}
+
+struct App {
+    modio: Modio,
+    path: String,
+    /// platform of the mods folder
+    platform: Platform,
+    xdg_config_home: String,
+    state: State,
+    installed: Vec<InstalledMod>,
+    subscriptions: Vec<Mod>,
+    search: Vec<Mod>,
+    pane: Pane,
+    lists: [ListState; 3],
+    /// the search being typed after pressing /
+    query: Option<String>,
+    /// the installed mod waiting for a second `r`
+    removing: Option<u64>,
+    /// running background operations, their latest progress and the bar the library advances
+    running: BTreeMap<usize, (String, ProgressBar)>,
+    next_op: usize,
+    status: String,
+    /// dependencies of mod.io mods, fetched when they are first shown
+    dependencies: HashMap<u64, Vec<u64>>,
+    requested: HashSet<u64>,
+    tx: UnboundedSender<Msg>,
+}
+
+/// run the full-screen interface until q is pressed
+pub async fn run(
+    modio: Modio,
+    path: String,
+    platform: Platform,
+    xdg_config_home: String,
+    installed: Vec<InstalledMod>,
+    state: State,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // background tasks hold non-send errors, so they run on this thread
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async move {
+            let (tx, rx) = unbounded_channel();
+            let mut app = App {
+                modio,
+                path,
+                platform,
+                xdg_config_home,
+                state,
+                installed,
+                subscriptions: Vec::new(),
+                search: Vec::new(),
+                pane: Pane::Installed,
+                lists: Default::default(),
+                query: None,
+                removing: None,
+                running: BTreeMap::new(),
+                next_op: 0,
+                status: String::new(),
+                dependencies: HashMap::new(),
+                requested: HashSet::new(),
+                tx,
+            };
+            app.load_subscriptions();
+            let mut terminal = ratatui::init();
+            let result = app.event_loop(&mut terminal, rx).await;
+            ratatui::restore();
+            app.state.save(&app.xdg_config_home)?;
+            result
+        })
+        .await
+}
+
+impl App {
+    async fn event_loop(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        mut rx: UnboundedReceiver<Msg>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        loop {
+            while let Ok(msg) = rx.try_recv() {
+                self.receive(msg)?;
+            }
+            self.fetch_dependencies();
+            terminal.draw(|frame| self.draw(frame))?;
+            if event::poll(Duration::ZERO)? {
+                if let Event::Key(key) = event::read()?
+                    && key.kind == KeyEventKind::Press
+                    && !self.key(key.code)?
+                {
+                    return Ok(());
+                }
+                continue;
+            }
+            // let the background tasks run
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    fn receive(&mut self, msg: Msg) -> Result<(), Box<dyn std::error::Error>> {
+        match msg {
+            Msg::Progress(op, x) => {
+                if let Some(running) = self.running.get_mut(&op) {
+                    running.0 = x;
+                }
+            }
+            Msg::Done(op, x) => {
+                self.running.remove(&op);
+                self.status = x;
+                self.installed =
+                    read_installed_mods(&self.path, self.platform, &ProgressBar::hidden())?.mods;
+                self.state.save(&self.xdg_config_home)?;
+            }
+            Msg::Subscriptions(x) => self.subscriptions = x,
+            Msg::Search(x) => {
+                self.search = x;
+                self.lists[2].select(Some(0));
+            }
+            Msg::Dependencies(mod_id, x) => {
+                self.dependencies.insert(mod_id, x);
+            }
+            Msg::Installed(x) => {
+                for (mod_id, dependencies) in x {
+                    self.state.dependencies.insert(mod_id, dependencies);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// handle a key, returns false to quit
+    fn key(&mut self, code: KeyCode) -> Result<bool, Box<dyn std::error::Error>> {
+        if let Some(query) = &mut self.query {
+            match code {
+                KeyCode::Enter => {
+                    let query = self.query.take().unwrap();
+                    self.pane = Pane::Search;
+                    self.search_mods(query);
+                }
+                KeyCode::Esc => self.query = None,
+                KeyCode::Backspace => {
+                    query.pop();
+                }
+                KeyCode::Char(x) => query.push(x),
+                _ => {}
+            }
+            return Ok(true);
+        }
+        let removing = self.removing.take();
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => {
+                // quitting drops the background tasks and what they would have recorded
+                if !self.running.is_empty() {
+                    self.status = format!(
+                        "wait for {} running operations to finish before quitting",
+                        self.running.len()
+                    );
+                    return Ok(true);
+                }
+                return Ok(false);
+            }
+            KeyCode::Tab => {
+                self.pane = match self.pane {
+                    Pane::Installed => Pane::Subscriptions,
+                    Pane::Subscriptions => Pane::Search,
+                    Pane::Search => Pane::Installed,
+                }
+            }
+            KeyCode::BackTab => {
+                self.pane = match self.pane {
+                    Pane::Installed => Pane::Search,
+                    Pane::Subscriptions => Pane::Installed,
+                    Pane::Search => Pane::Subscriptions,
+                }
+            }
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Char('/') => self.query = Some(String::new()),
+            KeyCode::Char('s') => self.load_subscriptions(),
+            KeyCode::Char('i') => match self.selected_online() {
+                Some(mod_) if self.is_installed(mod_.id.get()) => {
+                    self.status = format!("{} is already installed", mod_.name)
+                }
+                Some(mod_) => self.install(mod_.id.get(), mod_.name.clone()),
+                None => self.status = "select a mod.io mod to install".into(),
+            },
+            KeyCode::Char('u') => match self.selected_installed() {
+                Some(mod_) if self.state.pins.contains_key(&mod_.mod_id().unwrap()) => {
+                    self.status = format!("{} is pinned", mod_.barcode())
+                }
+                Some(mod_) => self.update(mod_.clone()),
+                None => self.status = "select an installed mod to update".into(),
+            },
+            KeyCode::Char('e') => {
+                if let Some(mod_) = self.selected_installed() {
+                    let mut manifest = mod_.manifest.clone();
+                    manifest.pallet_mut().active = !manifest.pallet().active;
+                    save_manifest(PathBuf::from(&self.path), &manifest)?;
+                    self.status = format!(
+                        "{} {}",
+                        if manifest.pallet().active {
+                            "enabled"
+                        } else {
+                            "disabled"
+                        },
+                        mod_.barcode()
+                    );
+                    self.installed =
+                        read_installed_mods(&self.path, self.platform, &ProgressBar::hidden())?
+                            .mods;
+                }
+            }
+            KeyCode::Char('p') => {
+                if let Some(mod_) = self.selected_installed() {
+                    let barcode = mod_.barcode().to_string();
+                    let target = mod_.target().clone();
+                    self.status = match self.state.pins.remove(&target.modId) {
+                        Some(_) => {
+                            self.state.rescan();
+                            format!("unpinned {}", barcode)
+                        }
+                        None => {
+                            self.state.pins.insert(target.modId, target.modfileId);
+                            format!("pinned {} at modfile {}", barcode, target.modfileId)
+                        }
+                    };
+                    self.state.save(&self.xdg_config_home)?;
+                }
+            }
+            KeyCode::Char('r') => {
+                if let Some(mod_) = self.selected_installed() {
+                    let mod_id = mod_.mod_id().unwrap();
+                    if removing != Some(mod_id) {
+                        let dependents = self.state.dependents(mod_id);
+                        let needed_by: Vec<&str> = self
+                            .installed
+                            .iter()
+                            .filter(|x| x.mod_id().is_some_and(|id| dependents.contains(&id)))
+                            .map(|x| x.barcode())
+                            .collect();
+                        self.status = match needed_by.is_empty() {
+                            true => format!("press r again to remove {}", mod_.barcode()),
+                            false => format!(
+                                "press r again to remove {}, it is needed by {}",
+                                mod_.barcode(),
+                                needed_by.join(", ")
+                            ),
+                        };
+                        self.removing = Some(mod_id);
+                        return Ok(true);
+                    }
+                    let barcode = mod_.barcode().to_string();
+                    remove_mod(&self.path, mod_)?;
+                    self.state.pins.remove(&mod_id);
+                    self.state.dependencies.remove(&mod_id);
+                    self.state.save(&self.xdg_config_home)?;
+                    self.installed =
+                        read_installed_mods(&self.path, self.platform, &ProgressBar::hidden())?
+                            .mods;
+                    self.status = format!("removed {}", barcode);
+                }
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    fn pane_index(&self) -> usize {
+        match self.pane {
+            Pane::Installed => 0,
+            Pane::Subscriptions => 1,
+            Pane::Search => 2,
+        }
+    }
+
+    fn pane_len(&self) -> usize {
+        match self.pane {
+            Pane::Installed => self.installed.len(),
+            Pane::Subscriptions => self.subscriptions.len(),
+            Pane::Search => self.search.len(),
+        }
+    }
+
+    fn move_selection(&mut self, by: isize) {
+        let len = self.pane_len();
+        if len == 0 {
+            return;
+        }
+        let list = &mut self.lists[self.pane_index()];
+        let current = list.selected().unwrap_or(0) as isize;
+        list.select(Some((current + by).clamp(0, len as isize - 1) as usize));
+    }
+
+    fn selected_installed(&self) -> Option<&InstalledMod> {
+        match self.pane {
+            Pane::Installed => self.installed.get(self.lists[0].selected()?),
+            _ => None,
+        }
+    }
+
+    fn selected_online(&self) -> Option<&Mod> {
+        match self.pane {
+            Pane::Subscriptions => self.subscriptions.get(self.lists[1].selected()?),
+            Pane::Search => self.search.get(self.lists[2].selected()?),
+            Pane::Installed => None,
+        }
+    }
+
+    fn is_installed(&self, mod_id: u64) -> bool {
+        self.installed.iter().any(|x| x.mod_id() == Some(mod_id))
+    }
+
+    /// a name for a mod id, from the installed mods or the mod.io lists
+    fn name_of(&self, mod_id: u64) -> String {
+        if let Some(x) = self.installed.iter().find(|x| x.mod_id() == Some(mod_id)) {
+            return x.barcode().to_string();
+        }
+        match self
+            .subscriptions
+            .iter()
+            .chain(self.search.iter())
+            .find(|x| x.id.get() == mod_id)
+        {
+            Some(x) => x.name.clone(),
+            None => format!("mod {}", mod_id),
+        }
+    }
+
+    /// start a background operation, returns its id
+    /// register a background operation, the library reports its progress through the bar
+    fn start(&mut self, description: String) -> (usize, ProgressBar) {
+        let op = self.next_op;
+        self.next_op += 1;
+        let pb = ProgressBar::hidden();
+        self.running.insert(op, (description, pb.clone()));
+        (op, pb)
+    }
+
+    fn load_subscriptions(&mut self) {
+        let (op, _) = self.start("reading subscriptions".into());
+        let modio = self.modio.clone();
+        let tx = self.tx.clone();
+        tokio::task::spawn_local(async move {
+            let filter = GameId::_in(BONELAB).and(Name::asc());
+            match modio.user().subscriptions(filter).collect().await {
+                Ok(x) => {
+                    let _ = tx.send(Msg::Subscriptions(x));
+                    let _ = tx.send(Msg::Done(op, "subscriptions loaded".into()));
+                }
+                Err(x) => {
+                    let _ = tx.send(Msg::Done(
+                        op,
+                        format!("could not read subscriptions: {}", x),
+                    ));
+                }
+            }
+        });
+    }
+
+    fn search_mods(&mut self, query: String) {
+        let (op, _) = self.start(format!("searching for {}", query));
+        let modio = self.modio.clone();
+        let tx = self.tx.clone();
+        tokio::task::spawn_local(async move {
+            let filter = Fulltext::eq(query).limit(50);
+            let search = modio.game(Id::new(BONELAB)).mods().search(filter);
+            match search.first_page().await {
+                Ok(x) => {
+                    let found = format!("found {} mods", x.len());
+                    let _ = tx.send(Msg::Search(x));
+                    let _ = tx.send(Msg::Done(op, found));
+                }
+                Err(x) => {
+                    let _ = tx.send(Msg::Done(op, format!("search failed: {}", x)));
+                }
+            }
+        });
+    }
+
+    /// fetch the dependency list of the selected mod.io mod the first time it is shown
+    fn fetch_dependencies(&mut self) {
+        let mod_id = match self.selected_online() {
+            Some(x) if x.dependencies => x.id.get(),
+            _ => return,
+        };
+        if !self.requested.insert(mod_id) {
+            return;
+        }
+        let modio = self.modio.clone();
+        let tx = self.tx.clone();
+        tokio::task::spawn_local(async move {
+            let modref = modio.mod_(Id::new(BONELAB), Id::new(mod_id));
+            if let Ok(x) = modref.dependencies().list().await {
+                let ids = x.iter().map(|x| x.mod_id.get()).collect();
+                let _ = tx.send(Msg::Dependencies(mod_id, ids));
+            }
+        });
+    }
+
+    fn install(&mut self, mod_id: u64, name: String) {
+        let (op, pb) = self.start(format!("installing {}", name));
+        let modio = self.modio.clone();
+        let path = self.path.clone();
+        let installed = self.installed.clone();
+        let platform = self.platform;
+        let tx = self.tx.clone();
+        tokio::task::spawn_local(async move {
+            let result: Result<String, Box<dyn std::error::Error>> = async {
+                let mod_ = modio.mod_(Id::new(BONELAB), Id::new(mod_id)).get().await?;
+                let planned = plan_installs(&modio, vec![mod_], &installed, platform).await?;
+                // the dependencies are recorded here and merged into the app state when received
+                let mut recorded = State::default();
+                let outcome = install_planned(
+                    &modio,
+                    &path,
+                    planned.installs,
+                    &mut recorded,
+                    platform,
+                    &pb,
+                )
+                .await;
+                let _ = tx.send(Msg::Installed(recorded.dependencies.into_iter().collect()));
+                let mut problems: Vec<String> = outcome
+                    .failed
+                    .into_iter()
+                    .map(|(mod_name, x)| format!("could not install {}: {}", mod_name, x))
+                    .collect();
+                problems.extend(
+                    planned
+                        .unavailable
+                        .into_iter()
+                        .map(|(id, _, x)| format!("dependency {} is not available: {}", id, x)),
+                );
+                match problems.is_empty() {
+                    true => Ok(format!("installed {}", name)),
+                    false => Ok(problems.join(", ")),
+                }
+            }
+            .await;
+            let status = result.unwrap_or_else(|x| format!("could not install {}: {}", name, x));
+            let _ = tx.send(Msg::Done(op, status));
+        });
+    }
+
+    fn update(&mut self, installed: InstalledMod) {
+        let (op, _) = self.start(format!("updating {}", installed.barcode()));
+        let modio = self.modio.clone();
+        let path = self.path.clone();
+        let tx = self.tx.clone();
+        tokio::task::spawn_local(async move {
+            let barcode = installed.barcode().to_string();
+            let result: Result<String, Box<dyn std::error::Error>> = async {
+                let update = match pending_update(&modio, &installed).await? {
+                    Some(x) => x,
+                    None => return Ok(format!("{} is up to date", barcode)),
+                };
+                let _ = tx.send(Msg::Progress(
+                    op,
+                    format!(
+                        "updating {} to {} ({})",
+                        barcode,
+                        update.modfile.version.clone().unwrap_or_default(),
+                        format_size(update.modfile.filesize)
+                    ),
+                ));
+                install_update(&modio, &path, &update).await?;
+                Ok(format!("updated {}", barcode))
+            }
+            .await;
+            let status = result.unwrap_or_else(|x| format!("could not update {}: {}", barcode, x));
+            let _ = tx.send(Msg::Done(op, status));
+        });
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, running, status] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(self.running.len() as u16),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [lists, details] =
+            Layout::horizontal([Constraint::Percentage(45), Constraint::Percentage(55)])
+                .areas(main);
+        let [installed, subscriptions, search] = Layout::vertical([
+            Constraint::Ratio(1, 3),
+            Constraint::Ratio(1, 3),
+            Constraint::Ratio(1, 3),
+        ])
+        .areas(lists);
+
+        let items: Vec<ListItem> = self
+            .installed
+            .iter()
+            .map(|x| {
+                let mut line = format!(
+                    "{} {}",
+                    x.barcode(),
+                    x.manifest.pallet().version.clone().unwrap_or_default()
+                );
+                if !x.manifest.pallet().active {
+                    line += " [disabled]";
+                }
+                if self.state.pins.contains_key(&x.mod_id().unwrap()) {
+                    line += " [pinned]";
+                }
+                ListItem::new(line)
+            })
+            .collect();
+        self.draw_list(frame, installed, "installed", items, Pane::Installed);
+        let items = self.online_items(&self.subscriptions);
+        self.draw_list(
+            frame,
+            subscriptions,
+            "subscriptions",
+            items,
+            Pane::Subscriptions,
+        );
+        let title = match &self.query {
+            Some(x) => format!("search: {}_", x),
+            None => "search (/)".into(),
+        };
+        let items = self.online_items(&self.search);
+        self.draw_list(frame, search, &title, items, Pane::Search);
+
+        let text = match (self.selected_installed(), self.selected_online()) {
+            (Some(x), _) => self.installed_details(x),
+            (_, Some(x)) => self.online_details(x),
+            _ => Vec::new(),
+        };
+        frame.render_widget(
+            Paragraph::new(text)
+                .wrap(Wrap { trim: false })
+                .block(Block::bordered().title("details")),
+            details,
+        );
+
+        let lines: Vec<Line> = self
+            .running
+            .values()
+            .map(|(description, pb)| match pb.length() {
+                Some(len) if len > 1 => Line::from(format!(
+                    "… {}: {} ({}/{})",
+                    description,
+                    pb.message(),
+                    pb.position(),
+                    len
+                )),
+                _ => Line::from(format!("… {}", description)),
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines), running);
+        let help = "tab pane  / search  i install  u update  e enable/disable  p pin  r remove  s subscriptions  q quit";
+        let status_line = match self.status.is_empty() {
+            true => help.to_string(),
+            false => format!("{}  |  {}", self.status, help),
+        };
+        frame.render_widget(
+            Paragraph::new(status_line).style(Style::new().add_modifier(Modifier::REVERSED)),
+            status,
+        );
+    }
+
+    fn online_items(&self, mods: &[Mod]) -> Vec<ListItem<'static>> {
+        mods.iter()
+            .map(|x| {
+                let installed = match self.is_installed(x.id.get()) {
+                    true => " [installed]",
+                    false => "",
+                };
+                ListItem::new(format!(
+                    "{} by {}{}",
+                    x.name, x.submitted_by.username, installed
+                ))
+            })
+            .collect()
+    }
+
+    fn draw_list(
+        &mut self,
+        frame: &mut Frame,
+        area: Rect,
+        title: &str,
+        items: Vec<ListItem>,
+        pane: Pane,
+    ) {
+        let focused = self.pane == pane;
+        let mut block = Block::bordered().title(format!("{} ({})", title, items.len()));
+        if focused {
+            block = block.border_style(Style::new().add_modifier(Modifier::BOLD));
+        }
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        let index = match pane {
+            Pane::Installed => 0,
+            Pane::Subscriptions => 1,
+            Pane::Search => 2,
+        };
+        if self.lists[index].selected().is_none() {
+            self.lists[index].select(Some(0));
+        }
+        frame.render_stateful_widget(list, area, &mut self.lists[index]);
+    }
+
+    fn installed_details(&self, mod_: &InstalledMod) -> Vec<Line<'static>> {
+        let pallet = &mod_.manifest.pallet();
+        let target = mod_.target();
+        let mut lines = Vec::new();
+        if let Some(listing) = mod_.manifest.mod_listing() {
+            lines.push(
+                Line::from(listing.title.clone().unwrap_or_default())
+                    .style(Style::new().add_modifier(Modifier::BOLD)),
+            );
+            lines.push(Line::from(format!(
+                "by {}",
+                listing.author.clone().unwrap_or_default()
+            )));
+        }
+        lines.push(Line::from(format!("barcode: {}", pallet.palletBarcode)));
+        lines.push(Line::from(format!(
+            "version: {}",
+            pallet.version.clone().unwrap_or_default()
+        )));
+        lines.push(Line::from(format!(
+            "mod {}, modfile {}",
+            target.modId, target.modfileId
+        )));
+        lines.push(Line::from(format!("enabled: {}", pallet.active)));
+        if let Some(modfile) = self.state.pins.get(&target.modId) {
+            lines.push(Line::from(format!("pinned at modfile {}", modfile)));
+        }
+        if let Some(dependencies) = self.state.dependencies.get(&target.modId)
+            && !dependencies.is_empty()
+        {
+            let names: Vec<String> = dependencies.iter().map(|x| self.name_of(*x)).collect();
+            lines.push(Line::from(format!("depends on: {}", names.join(", "))));
+        }
+        let needed_by: Vec<String> = self
+            .state
+            .dependencies
+            .iter()
+            .filter(|(_, x)| x.contains(&target.modId))
+            .map(|(x, _)| self.name_of(*x))
+            .collect();
+        if !needed_by.is_empty() {
+            lines.push(Line::from(format!("needed by: {}", needed_by.join(", "))));
+        }
+        if let Some(description) = mod_
+            .manifest
+            .mod_listing()
+            .as_ref()
+            .and_then(|x| x.description.clone())
+        {
+            lines.push(Line::from(""));
+            lines.extend(description.lines().map(|x| Line::from(x.to_string())));
+        }
+        lines
+    }
+
+    fn online_details(&self, mod_: &Mod) -> Vec<Line<'static>> {
+        let mut lines = vec![
+            Line::from(mod_.name.clone()).style(Style::new().add_modifier(Modifier::BOLD)),
+            Line::from(format!("by {}", mod_.submitted_by.username)),
+            Line::from(format!("mod {}", mod_.id)),
+            Line::from(mod_.summary.clone()),
+        ];
+        if let Some(modfile) = &mod_.modfile {
+            lines.push(Line::from(format!(
+                "live version: {} ({})",
+                modfile.version.clone().unwrap_or_default(),
+                format_size(modfile.filesize)
+            )));
+        }
+        if mod_.dependencies {
+            match self.dependencies.get(&mod_.id.get()) {
+                Some(x) => {
+                    let names: Vec<String> = x.iter().map(|x| self.name_of(*x)).collect();
+                    lines.push(Line::from(format!("depends on: {}", names.join(", "))));
+                }
+                None => lines.push(Line::from("depends on: loading...")),
+            }
+        }
+        if let Some(changelog) = mod_.modfile.as_ref().and_then(|x| x.changelog.clone()) {
+            lines.push(Line::from(""));
+            lines.push(Line::from("changelog:"));
+            lines.extend(changelog.lines().map(|x| Line::from(x.to_string())));
+        }
+        lines
+    }
+}
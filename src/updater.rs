@@ -0,0 +1,375 @@
+//! finding and installing newer modfiles of installed mods
+
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use indicatif::ProgressBar;
+use modio::filter::prelude::*;
+use modio::{Modio, files::File, files::filters::Id as fid, mods::Mod, types::id::Id};
+
+use crate::client::{
+    BONELAB, changed_since, fetch_mods, find_missing_mod, is_for, live_modfile_id,
+    take_live_modfile,
+};
+use crate::installer::{DownloadOptions, download_mod};
+use crate::manifest::{InstalledMod, metadata_changes, refreshed_manifest};
+use crate::plan::{Action, Plan};
+use crate::state::{State, Unavailable};
+use crate::structs::Manifest;
+use crate::throw;
+
+/// older update cursors are not trusted and a full scan is done instead
+pub const EVENTS_MAX_AGE: i64 = 30 * 24 * 60 * 60;
+
+/// an installed mod with a newer live modfile on mod.io
+pub struct PendingUpdate<'a> {
+    /// the mod in the mods folder
+    pub installed: &'a InstalledMod,
+    /// the mod on mod.io
+    pub online_mod: Mod,
+    /// the live modfile for the platform of the mods folder
+    pub modfile: File,
+}
+
+/// a pinned mod that would have been updated
+pub struct HeldBack<'a> {
+    /// the mod in the mods folder
+    pub installed: &'a InstalledMod,
+    /// modfile id it is pinned at
+    pub pinned: u64,
+    /// version of the live modfile, if mod.io sent it along
+    pub version: Option<String>,
+    /// id of the live modfile
+    pub modfile_id: u64,
+}
+
+/// what `check_updates` found
+pub struct UpdateCheck<'a> {
+    /// mods to update
+    pub updates: Vec<PendingUpdate<'a>>,
+    /// pinned mods with updates available
+    pub held_back: Vec<HeldBack<'a>>,
+    /// barcodes of mods that could not be checked, and why
+    pub skipped: Vec<(String, String)>,
+    /// how many mods were looked at
+    pub checked: usize,
+    /// how many of them changed since the last check, none for a full scan
+    pub changed: Option<usize>,
+    /// unix time the check started
+    pub started: i64,
+}
+
+impl UpdateCheck<'_> {
+    /// the update cursor to save once the updates are installed
+    ///
+    /// when a mod was skipped the old cursor is kept, so the events that made it a candidate
+    /// are looked at again on the next check
+    pub fn next_sync(&self, last_sync: Option<i64>) -> Option<i64> {
+        match self.skipped.is_empty() {
+            true => Some(self.started),
+            false => last_sync,
+        }
+    }
+}
+
+/// find installed mods with newer live modfiles
+///
+/// unless `full_scan` is set, only mods with mod.io events since `state.last_sync` are looked at,
+/// mods mod.io does not serve anymore are recorded in `state.unavailable`
+pub async fn check_updates<'a>(
+    modio: &Modio,
+    installed_mods: &'a [InstalledMod],
+    state: &mut State,
+    full_scan: bool,
+    pb: &ProgressBar,
+) -> Result<UpdateCheck<'a>, Box<dyn std::error::Error>> {
+    let started = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    let mut ids: Vec<u64> = installed_mods.iter().filter_map(|x| x.mod_id()).collect();
+    let checked = ids.len();
+    let mut changed = None;
+    if let Some(last_sync) = state.last_sync
+        && !full_scan
+        && started - last_sync < EVENTS_MAX_AGE
+    {
+        let since = changed_since(modio, &ids, last_sync).await?;
+        ids.retain(|x| since.contains(x));
+        changed = Some(ids.len());
+    }
+    let mut online_mods = fetch_mods(modio, &ids).await?;
+    let mut check = UpdateCheck {
+        updates: Vec::new(),
+        held_back: Vec::new(),
+        skipped: Vec::new(),
+        checked,
+        changed,
+        started,
+    };
+    pb.set_length(installed_mods.len() as u64);
+    for mod_ in installed_mods {
+        pb.inc(1);
+        pb.set_message(format!("Checking {}", mod_.barcode()));
+        let target = mod_.target();
+        if !ids.contains(&target.modId) {
+            continue;
+        }
+        let online_mod = match online_mods.remove(&target.modId) {
+            Some(x) => Ok(x),
+            // search results only have public mods, find out what happened to it
+            None => match find_missing_mod(modio, target.modId).await {
+                Ok(x) => x,
+                Err(x) => {
+                    check
+                        .skipped
+                        .push((mod_.barcode().to_string(), x.to_string()));
+                    continue;
+                }
+            },
+        };
+        let mut online_mod = match online_mod {
+            Ok(x) => {
+                state.unavailable.remove(&target.modId);
+                x
+            }
+            Err(reason) => {
+                let entry = state
+                    .unavailable
+                    .entry(target.modId)
+                    .or_insert(Unavailable {
+                        barcode: mod_.barcode().to_string(),
+                        reason,
+                        since: started,
+                        kept: false,
+                    });
+                entry.reason = reason;
+                continue;
+            }
+        };
+        let live = match live_modfile_id(&online_mod, mod_.platform) {
+            Some(x) if x != target.modfileId => x,
+            _ => continue,
+        };
+        if let Some(pinned) = state.pins.get(&target.modId) {
+            let version = online_mod
+                .modfile
+                .as_ref()
+                .filter(|x| x.id.get() == live)
+                .and_then(|x| x.version.clone());
+            check.held_back.push(HeldBack {
+                installed: mod_,
+                pinned: *pinned,
+                version,
+                modfile_id: live,
+            });
+            continue;
+        }
+        let modfile = match take_live_modfile(modio, &mut online_mod, mod_.platform).await? {
+            Some(x) if is_for(&x, mod_.platform) => x,
+            _ => {
+                check.skipped.push((
+                    mod_.barcode().to_string(),
+                    format!("no {} modfile", mod_.platform),
+                ));
+                continue;
+            }
+        };
+        check.updates.push(PendingUpdate {
+            installed: mod_,
+            online_mod,
+            modfile,
+        });
+    }
+    pb.finish_and_clear();
+    Ok(check)
+}
+
+/// every modfile between the installed one and the live one for the platform, newest first
+pub async fn changelogs(
+    modio: &Modio,
+    update: &PendingUpdate<'_>,
+) -> Result<Vec<File>, Box<dyn std::error::Error>> {
+    let installed = update.installed.target().modfileId;
+    let filter = fid::gt(installed)
+        .and(fid::le(update.modfile.id.get()))
+        .order_by(fid::desc());
+    let files = modio
+        .mod_(Id::new(BONELAB), update.online_mod.id)
+        .files()
+        .search(filter)
+        .collect()
+        .await?;
+    Ok(files
+        .into_iter()
+        .filter(|x| is_for(x, update.installed.platform))
+        .collect())
+}
+
+/// the downloads and replaced folders of a set of updates
+pub fn update_plan(updates: &[PendingUpdate]) -> Plan {
+    let mut plan = Plan::default();
+    for update in updates {
+        plan.install(
+            &update.online_mod.name,
+            update.modfile.filesize,
+            update.modfile.filesize_uncompressed,
+            Some(update.installed.barcode()),
+        );
+    }
+    plan
+}
+
+/// the update of one installed mod, none when it is at the live modfile for its platform
+pub async fn pending_update<'a>(
+    modio: &Modio,
+    installed: &'a InstalledMod,
+) -> Result<Option<PendingUpdate<'a>>, Box<dyn std::error::Error>> {
+    let target = installed.target();
+    let mut online_mod = modio
+        .mod_(Id::new(BONELAB), Id::new(target.modId))
+        .get()
+        .await?;
+    let modfile = match take_live_modfile(modio, &mut online_mod, installed.platform).await? {
+        Some(x) if is_for(&x, installed.platform) && x.id.get() != target.modfileId => x,
+        _ => return Ok(None),
+    };
+    Ok(Some(PendingUpdate {
+        installed,
+        online_mod,
+        modfile,
+    }))
+}
+
+/// install the new modfile of an update, keeping what the old manifest knew
+pub async fn install_update(
+    modio: &Modio,
+    path: &str,
+    update: &PendingUpdate<'_>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let installed = update.installed;
+    let mut manifest = refreshed_manifest(&installed.manifest, &update.online_mod, &update.modfile);
+    manifest.pallet_mut().updateDate =
+        (update.online_mod.date_updated.as_secs() * 1000).to_string();
+    if let Some(target) = manifest.mod_target_mut(installed.platform) {
+        target.modfileId = update.modfile.id.into();
+    }
+    download_mod(
+        &update.online_mod,
+        modio,
+        Path::new(path),
+        installed.platform,
+        DownloadOptions {
+            modfile: Some(&update.modfile),
+            manifest: Some(manifest),
+            installed_date: installed.manifest.pallet().installedDate.parse().ok(),
+        },
+    )
+    .await
+}
+
+/// manifests rewritten with current mod.io metadata
+pub struct MetadataRefresh {
+    /// one manifest write per changed mod, with the fields that change
+    pub plan: Plan,
+    /// the manifests to write
+    pub manifests: Vec<Manifest>,
+    /// modfiles that could not be fetched, and why
+    pub skipped: Vec<(u64, String)>,
+}
+
+/// fetch the metadata of every installed mod and the manifests that would change,
+/// the pallet folders are not touched
+pub async fn refresh_metadata(
+    modio: &Modio,
+    installed_mods: &[InstalledMod],
+) -> Result<MetadataRefresh, Box<dyn std::error::Error>> {
+    let ids: Vec<u64> = installed_mods.iter().filter_map(|x| x.mod_id()).collect();
+    let mut online_mods = fetch_mods(modio, &ids).await?;
+    let mut refresh = MetadataRefresh {
+        plan: Plan::default(),
+        manifests: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for installed in installed_mods {
+        let target = installed.target();
+        let online_mod = match online_mods.get_mut(&target.modId) {
+            Some(x) => x,
+            None => continue,
+        };
+        // the installed modfile, which is not always the live one
+        let modfile = match &online_mod.modfile {
+            Some(x) if x.id.get() == target.modfileId => online_mod.modfile.take().unwrap(),
+            _ => {
+                let modref = modio.mod_(Id::new(BONELAB), online_mod.id);
+                match modref.file(Id::new(target.modfileId)).get().await {
+                    Ok(x) => x,
+                    Err(x) => {
+                        refresh.skipped.push((target.modfileId, x.to_string()));
+                        continue;
+                    }
+                }
+            }
+        };
+        let manifest = refreshed_manifest(&installed.manifest, online_mod, &modfile);
+        let changes = metadata_changes(&installed.manifest, &manifest);
+        if changes.is_empty() {
+            continue;
+        }
+        refresh.plan.actions.push(Action::WriteManifest(format!(
+            "{} ({})",
+            installed.barcode(),
+            changes.join(", ")
+        )));
+        refresh.manifests.push(manifest);
+    }
+    Ok(refresh)
+}
+
+/// an older modfile to reinstall
+pub struct Rollback {
+    /// the mod on mod.io
+    pub online_mod: Mod,
+    /// the modfile to go back to
+    pub modfile: File,
+    /// the manifest to write along with it
+    pub manifest: Manifest,
+}
+
+/// find the modfile `to` names, by id or version, or the one before the installed one
+///
+/// returns none when that modfile is already installed
+pub async fn rollback_target(
+    modio: &Modio,
+    installed: &InstalledMod,
+    to: &Option<String>,
+) -> Result<Option<Rollback>, Box<dyn std::error::Error>> {
+    let target = installed.target();
+    let modref = modio.mod_(Id::new(BONELAB), Id::new(target.modId));
+    let online_mod = modref.clone().get().await?;
+    let files = modref.files().search(fid::desc()).collect().await?;
+    let mut files = files
+        .into_iter()
+        .filter(|file| is_for(file, installed.platform));
+    let modfile = match to {
+        Some(to) => files.find(|file| {
+            file.id.to_string() == *to || file.version.as_deref() == Some(to.as_str())
+        }),
+        None => files.find(|file| file.id.get() < target.modfileId),
+    };
+    let modfile = match modfile {
+        Some(x) => x,
+        None => throw("No matching older modfile")?,
+    };
+    if modfile.id.get() == target.modfileId {
+        return Ok(None);
+    }
+    let mut manifest = refreshed_manifest(&installed.manifest, &online_mod, &modfile);
+    if let Some(target) = manifest.mod_target_mut(installed.platform) {
+        target.modfileId = modfile.id.into();
+    }
+    Ok(Some(Rollback {
+        online_mod,
+        modfile,
+        manifest,
+    }))
+}
@@ -0,0 +1,71 @@
+//! install order of mods and their mod.io dependencies
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use bonelab_mod_manager::installer::order_dependencies;
+use bonelab_mod_manager::sync::needed_mods;
+
+fn graph(mods: &[(u64, &str, &[u64])]) -> HashMap<u64, (String, Vec<u64>)> {
+    mods.iter()
+        .map(|(id, name, dependencies)| (*id, (name.to_string(), dependencies.to_vec())))
+        .collect()
+}
+
+fn position(order: &[(u64, Option<String>)], id: u64) -> usize {
+    order.iter().position(|x| x.0 == id).unwrap()
+}
+
+#[test]
+fn dependencies_come_first() {
+    let graph = graph(&[(1, "Gun", &[2]), (2, "Lib", &[])]);
+    let ordered = order_dependencies(&[1], &graph);
+    assert_eq!(ordered.order, vec![(2, Some("Gun".into())), (1, None)]);
+    assert!(ordered.cycles.is_empty());
+}
+
+#[test]
+fn diamond_dependencies_are_installed_once() {
+    // 1 needs 2 and 3, which both need 4
+    let graph = graph(&[
+        (1, "Pack", &[2, 3]),
+        (2, "Left", &[4]),
+        (3, "Right", &[4]),
+        (4, "Base", &[]),
+    ]);
+    let ordered = order_dependencies(&[1], &graph);
+    assert!(ordered.cycles.is_empty());
+    assert_eq!(ordered.order.len(), 4);
+    assert!(position(&ordered.order, 4) < position(&ordered.order, 2));
+    assert!(position(&ordered.order, 4) < position(&ordered.order, 3));
+    assert!(position(&ordered.order, 2) < position(&ordered.order, 1));
+    assert!(position(&ordered.order, 3) < position(&ordered.order, 1));
+    assert_eq!(
+        ordered.order[position(&ordered.order, 4)].1.as_deref(),
+        Some("Left")
+    );
+}
+
+#[test]
+fn cycles_are_reported_and_broken() {
+    let graph = graph(&[(1, "A", &[2]), (2, "B", &[3]), (3, "C", &[1])]);
+    let ordered = order_dependencies(&[1], &graph);
+    assert_eq!(ordered.cycles, vec![vec!["A", "B", "C", "A"]]);
+    let ids: Vec<u64> = ordered.order.iter().map(|x| x.0).collect();
+    assert_eq!(ids, vec![3, 2, 1]);
+}
+
+#[test]
+fn installed_dependencies_are_skipped() {
+    // 2 is not in the graph because it is already installed
+    let graph = graph(&[(1, "Gun", &[2])]);
+    let ordered = order_dependencies(&[1], &graph);
+    assert_eq!(ordered.order, vec![(1, None)]);
+}
+
+#[test]
+fn sync_keeps_dependencies_of_locked_mods() {
+    // locked mod 1 depends on 2, which depends on 3, 4 is not needed by anything
+    let dependencies = BTreeMap::from([(1, vec![2]), (2, vec![3]), (4, vec![])]);
+    let needed = needed_mods(&[1], &dependencies);
+    assert_eq!(needed, BTreeSet::from([1, 2, 3]));
+}
@@ -0,0 +1,34 @@
+//! the disk space check before a plan with downloads is applied
+
+use bonelab_mod_manager::plan::{Action, Plan, disk};
+
+fn download(size: u64, unpacked: u64) -> Plan {
+    Plan {
+        actions: vec![Action::Download {
+            name: "Gun".into(),
+            size,
+            unpacked,
+        }],
+    }
+}
+
+#[test]
+fn cache_and_mods_on_one_filesystem_need_room_for_both() {
+    let tmp = std::env::temp_dir();
+    let mods = tmp.to_string_lossy().into_owned();
+    // the cache folder does not exist yet, its closest parent is checked
+    let cache = tmp.join("bmm-no-such-cache").to_string_lossy().into_owned();
+    let Some(free) = disk(&mods).map(|x| x.free) else {
+        // no df here
+        return;
+    };
+    // either one fits, both together do not
+    assert!(
+        download(free / 4, free / 4)
+            .short_disks(&mods, &cache)
+            .is_empty()
+    );
+    let short = download(free, free).short_disks(&mods, &cache);
+    assert_eq!(short.len(), 1);
+    assert_eq!(short[0].1, free * 2);
+}
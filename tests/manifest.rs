@@ -0,0 +1,161 @@
+//! golden-file tests for reading and writing `.manifest` files
+
+use std::fs;
+
+use bonelab_mod_manager::structs::{MANIFEST_VERSION, Manifest, ManifestObject, Platform};
+
+fn fixture(name: &str) -> String {
+    fs::read_to_string(format!(
+        "{}/tests/fixtures/manifests/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+}
+
+/// parse a fixture and check that writing it back gives the same file
+fn round_trip(name: &str) -> Manifest {
+    let json = fixture(name);
+    let manifest = Manifest::parse(&json).unwrap();
+    assert_eq!(
+        serde_json::to_string_pretty(&manifest).unwrap(),
+        json.trim_end(),
+        "{} did not round trip",
+        name
+    );
+    manifest
+}
+
+#[test]
+fn every_valid_fixture_round_trips() {
+    let dir = format!(
+        "{}/tests/fixtures/manifests/valid",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let mut count = 0;
+    for entry in fs::read_dir(dir).unwrap() {
+        let name = entry.unwrap().file_name().to_string_lossy().into_owned();
+        if name.ends_with(".manifest") {
+            round_trip(&format!("valid/{}", name));
+            count += 1;
+        }
+    }
+    assert!(count >= 4);
+}
+
+#[test]
+fn pc_manifest_round_trips() {
+    let manifest = round_trip("valid/pc.manifest");
+    assert_eq!(manifest.version, MANIFEST_VERSION);
+    assert_eq!(manifest.pallet().palletBarcode, "Author.Mod");
+    assert_eq!(
+        manifest.mod_listing().unwrap().title.as_deref(),
+        Some("Mod")
+    );
+    let target = manifest.mod_target(Platform::Pc).unwrap();
+    assert_eq!((target.modId, target.modfileId), (1234, 5678));
+}
+
+#[test]
+fn quest_manifest_round_trips() {
+    let manifest = round_trip("valid/quest.manifest");
+    assert!(
+        manifest
+            .pallet()
+            .palletPath
+            .starts_with(Platform::Android.game_mod_folder())
+    );
+    assert!(manifest.mod_target(Platform::Pc).is_none());
+    assert_eq!(
+        manifest.mod_target(Platform::Android).unwrap().modfileId,
+        5679
+    );
+}
+
+#[test]
+fn local_manifest_round_trips() {
+    let manifest = round_trip("valid/local.manifest");
+    assert!(manifest.pallet().local);
+    assert!(!manifest.pallet().active);
+    assert!(manifest.mod_target(Platform::Pc).is_none());
+}
+
+#[test]
+fn unknown_objects_and_fields_round_trip() {
+    let manifest = round_trip("valid/multi_target.manifest");
+    assert_eq!(manifest.mod_listing().unwrap().targets.len(), 2);
+    assert_eq!(manifest.mod_target(Platform::Pc).unwrap().modfileId, 100);
+    assert_eq!(
+        manifest.mod_target(Platform::Android).unwrap().modfileId,
+        101
+    );
+    assert!(manifest.pallet().extra.contains_key("favorite"));
+    assert!(manifest.extra.contains_key("types"));
+    assert!(matches!(
+        manifest.objects["4"],
+        ManifestObject::ModTarget(_)
+    ));
+    assert!(matches!(manifest.objects["5"], ManifestObject::Other(_)));
+}
+
+#[test]
+fn edits_keep_unknown_fields() {
+    let mut manifest = Manifest::parse(&fixture("valid/multi_target.manifest")).unwrap();
+    manifest.pallet_mut().active = false;
+    let written = serde_json::to_string_pretty(&manifest).unwrap();
+    let reread = Manifest::parse(&written).unwrap();
+    assert!(!reread.pallet().active);
+    assert_eq!(reread.pallet().extra["favorite"], true);
+    match &reread.objects["4"] {
+        ManifestObject::ModTarget(x) => assert_eq!(x.extra["fileSize"], 2048),
+        x => panic!("object 4 is not a mod target: {:?}", x),
+    }
+}
+
+#[test]
+fn newer_versions_are_refused() {
+    let error = Manifest::parse(&fixture("invalid/future.manifest")).unwrap_err();
+    assert!(error.to_string().contains("newer"), "{}", error);
+}
+
+#[test]
+fn older_versions_are_refused() {
+    let error = Manifest::parse(&fixture("invalid/older.manifest")).unwrap_err();
+    assert!(error.to_string().contains("older"), "{}", error);
+}
+
+#[test]
+fn manifests_need_a_version() {
+    let error = Manifest::parse(&fixture("invalid/no_version.manifest")).unwrap_err();
+    assert!(error.to_string().contains("no version"), "{}", error);
+}
+
+#[test]
+fn new_manifests_have_a_target_per_platform() {
+    let manifest = Manifest::parse(&fixture("valid/multi_target.manifest")).unwrap();
+    let targets = Platform::ALL
+        .into_iter()
+        .map(|x| (x, manifest.mod_target(x).unwrap().clone()))
+        .collect();
+    let built = Manifest::new(
+        manifest.pallet().clone(),
+        manifest.mod_listing().cloned(),
+        targets,
+    );
+    let reread = Manifest::parse(&serde_json::to_string(&built).unwrap()).unwrap();
+    assert_eq!(reread.mod_target(Platform::Pc).unwrap().modfileId, 100);
+    assert_eq!(reread.mod_target(Platform::Android).unwrap().modfileId, 101);
+}
+
+#[test]
+fn deserializing_checks_the_root() {
+    let json = fixture("valid/pc.manifest").replacen("\"ref\": \"1\"", "\"ref\": \"2\"", 1);
+    let error = serde_json::from_str::<Manifest>(&json).unwrap_err();
+    assert!(error.to_string().contains("not a pallet"), "{}", error);
+}
+
+#[test]
+fn deserializing_checks_the_version() {
+    let error = serde_json::from_str::<Manifest>(&fixture("invalid/future.manifest")).unwrap_err();
+    assert!(error.to_string().contains("newer"), "{}", error);
+}
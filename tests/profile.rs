@@ -0,0 +1,70 @@
+//! activating profiles on a mods folder with mod.io and local mods
+
+use std::fs;
+
+use bonelab_mod_manager::manifest::{InstalledMods, read_installed_mods};
+use bonelab_mod_manager::profile::plan_activation;
+use bonelab_mod_manager::state::State;
+use bonelab_mod_manager::structs::Platform;
+use indicatif::ProgressBar;
+
+/// a mods folder with the pc fixture (Author.Mod, active) and the local one (Tester.Gun, disabled)
+fn mods_folder(name: &str) -> InstalledMods {
+    let fixtures = format!(
+        "{}/tests/fixtures/manifests/valid",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let folder = std::env::temp_dir().join(format!("bmm-profile-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&folder).unwrap();
+    for fixture in ["pc.manifest", "local.manifest"] {
+        fs::copy(format!("{}/{}", fixtures, fixture), folder.join(fixture)).unwrap();
+    }
+    let path = folder.to_string_lossy().into_owned() + "/";
+    let installed = read_installed_mods(&path, Platform::Pc, &ProgressBar::hidden()).unwrap();
+    fs::remove_dir_all(folder).unwrap();
+    installed
+}
+
+fn barcodes(mods: &[&bonelab_mod_manager::manifest::InstalledMod]) -> Vec<String> {
+    mods.iter().map(|x| x.barcode().to_string()).collect()
+}
+
+#[test]
+fn local_mods_are_read_apart() {
+    let installed = mods_folder("read");
+    assert_eq!(installed.mods.len(), 1);
+    assert_eq!(installed.local.len(), 1);
+    assert_eq!(installed.local[0].barcode(), "Tester.Gun");
+    assert_eq!(installed.local[0].mod_id(), None);
+}
+
+#[test]
+fn local_mods_are_enabled_by_barcode() {
+    let installed = mods_folder("enable");
+    let profile = vec!["1234".to_string(), "Tester.Gun".to_string()];
+    let activation = plan_activation(
+        &profile,
+        &installed.mods,
+        &installed.local,
+        &State::default(),
+        false,
+    );
+    assert_eq!(barcodes(&activation.enable), vec!["Tester.Gun"]);
+    assert!(activation.disable.is_empty());
+    assert!(activation.unknown.is_empty());
+}
+
+#[test]
+fn local_mods_outside_the_profile_are_removed() {
+    let installed = mods_folder("remove");
+    let profile = vec!["1234".to_string()];
+    let activation = plan_activation(
+        &profile,
+        &installed.mods,
+        &installed.local,
+        &State::default(),
+        true,
+    );
+    assert_eq!(barcodes(&activation.remove), vec!["Tester.Gun"]);
+    assert!(activation.enable.is_empty());
+}
@@ -0,0 +1,27 @@
+//! the update cursor `-u` saves after checking for updates
+
+use bonelab_mod_manager::updater::UpdateCheck;
+
+fn check(skipped: Vec<(String, String)>) -> UpdateCheck<'static> {
+    UpdateCheck {
+        updates: Vec::new(),
+        held_back: Vec::new(),
+        skipped,
+        checked: 2,
+        changed: Some(2),
+        started: 2000,
+    }
+}
+
+#[test]
+fn cursor_moves_when_every_mod_was_checked() {
+    assert_eq!(check(Vec::new()).next_sync(Some(1000)), Some(2000));
+    assert_eq!(check(Vec::new()).next_sync(None), Some(2000));
+}
+
+#[test]
+fn cursor_stays_when_a_mod_was_skipped() {
+    let skipped = vec![("Author.Gun".to_string(), "no windows modfile".to_string())];
+    assert_eq!(check(skipped.clone()).next_sync(Some(1000)), Some(1000));
+    assert_eq!(check(skipped).next_sync(None), None);
+}
@@ -0,0 +1,35 @@
+//! mod.io version strings and the semver requirements of `sync`
+
+use bonelab_mod_manager::client::{parse_version, version_matches};
+use semver::{Version, VersionReq};
+
+#[test]
+fn short_versions_are_padded() {
+    assert_eq!(parse_version("v1.2"), Some(Version::new(1, 2, 0)));
+    assert_eq!(parse_version("V3"), Some(Version::new(3, 0, 0)));
+    assert_eq!(parse_version(" 1.2.3 "), Some(Version::new(1, 2, 3)));
+    assert_eq!(parse_version("beta"), None);
+}
+
+#[test]
+fn requirements_match_mod_io_versions() {
+    let requirement = Some(VersionReq::parse("^1.2").unwrap());
+    assert!(version_matches(&requirement, &Some("v1.2".into())));
+    assert!(version_matches(&requirement, &Some("1.9.1".into())));
+}
+
+#[test]
+fn requirement_misses() {
+    let requirement = Some(VersionReq::parse("^1.2").unwrap());
+    assert!(!version_matches(&requirement, &Some("1.1".into())));
+    assert!(!version_matches(&requirement, &Some("2.0.0".into())));
+    // a version that is not semver never matches a requirement
+    assert!(!version_matches(&requirement, &Some("beta".into())));
+    assert!(!version_matches(&requirement, &None));
+}
+
+#[test]
+fn no_requirement_matches_anything() {
+    assert!(version_matches(&None, &None));
+    assert!(version_matches(&None, &Some("beta".into())));
+}